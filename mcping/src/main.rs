@@ -0,0 +1,397 @@
+use std::{process::ExitCode, time::Duration};
+
+use clap::{Parser, ValueEnum};
+use mcstatus::{PingError, PingOptions, ServerStatus};
+use serde_json::json;
+
+/// Gets the status of Minecraft servers
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+    /// Servers to query, as host or host:port
+    #[arg(required = true)]
+    addresses: Vec<String>,
+
+    /// How to print the results
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Seconds to wait for each server before treating it as offline
+    #[arg(short, long, default_value_t = 5.0)]
+    timeout: f64,
+
+    /// Protocol version to send in the handshake, -1 to not care
+    #[arg(short, long, default_value_t = -1, allow_hyphen_values = true)]
+    protocol: i32,
+
+    /// Latency in milliseconds above which a Nagios check warns
+    #[arg(short, long)]
+    warning: Option<u64>,
+
+    /// Latency in milliseconds above which a Nagios check is critical
+    #[arg(short, long)]
+    critical: Option<u64>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// Human readable summary
+    Text,
+    /// A JSON array with one object per server
+    Json,
+    /// Nagios/Icinga plugin output and exit codes
+    Nagios,
+}
+
+// Exit codes Nagios and Icinga expect from plugins
+const NAGIOS_OK: u8 = 0;
+const NAGIOS_WARNING: u8 = 1;
+const NAGIOS_CRITICAL: u8 = 2;
+const NAGIOS_UNKNOWN: u8 = 3;
+
+type Outcome = (String, Result<ServerStatus, PingError>);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            // Nagios treats clap's usual exit code of 2 as CRITICAL, when it's the check that's wrong
+            return ExitCode::from(match err.use_stderr() && wants_nagios(std::env::args()) {
+                true => NAGIOS_UNKNOWN,
+                false => err.exit_code() as u8,
+            });
+        }
+    };
+
+    let timeout = match timeout(args.timeout) {
+        Some(timeout) => timeout,
+        None => {
+            eprintln!("Timeout must be a positive number of seconds");
+            return ExitCode::from(match args.format {
+                Format::Nagios => NAGIOS_UNKNOWN,
+                _ => 2,
+            });
+        }
+    };
+
+    let options = PingOptions {
+        protocol_version: args.protocol,
+        timeout: Some(timeout),
+    };
+
+    // Query every server at once, keeping results in the order they were given
+    let results: Vec<Outcome> = futures::future::join_all(
+        args.addresses
+            .iter()
+            .map(|addr| async { (addr.clone(), mcstatus::ping_with(addr, &options).await) }),
+    )
+    .await;
+
+    let (output, code) = match args.format {
+        Format::Text => (text(&results), all_online(&results)),
+        Format::Json => (json(&results), all_online(&results)),
+        Format::Nagios => nagios(&results, args.warning, args.critical),
+    };
+    print!("{}", output);
+
+    ExitCode::from(code)
+}
+
+// Whether the command line asks for Nagios output, for when the rest of it can't be parsed
+fn wants_nagios(args: impl IntoIterator<Item = String>) -> bool {
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        let format = match arg.as_str() {
+            "--format" | "-f" => args.next(),
+            _ => arg
+                .strip_prefix("--format=")
+                .or_else(|| arg.strip_prefix("-f"))
+                .map(|f| f.trim_start_matches('=').to_string()),
+        };
+        if format.as_deref() == Some("nagios") {
+            return true;
+        }
+    }
+    false
+}
+
+// The timeout in seconds as a Duration, if it's positive and not too long to hold
+fn timeout(secs: f64) -> Option<Duration> {
+    match Duration::try_from_secs_f64(secs) {
+        Ok(timeout) if !timeout.is_zero() => Some(timeout),
+        _ => None,
+    }
+}
+
+fn latency_ms(status: &ServerStatus) -> Option<u64> {
+    status.latency.map(|latency| latency.as_millis() as u64)
+}
+
+// Exit code for text and JSON output: 0 if every server is online, otherwise 1
+fn all_online(results: &[Outcome]) -> u8 {
+    match results.iter().all(|(_, result)| result.is_ok()) {
+        true => 0,
+        false => 1,
+    }
+}
+
+fn text(results: &[Outcome]) -> String {
+    let mut out = String::new();
+
+    for (addr, result) in results {
+        match result {
+            Ok(status) => {
+                match latency_ms(status) {
+                    Some(ms) => out.push_str(&format!("{} - online ({} ms)\n", addr, ms)),
+                    None => out.push_str(&format!("{} - online\n", addr)),
+                }
+                out.push_str(&format!(
+                    "  Version: {} (protocol {})\n",
+                    status.version.name, status.version.protocol
+                ));
+                if let Some(players) = &status.players {
+                    let names: Vec<&str> = players.sample.iter().map(|p| p.name.as_str()).collect();
+                    match names.is_empty() {
+                        true => out
+                            .push_str(&format!("  Players: {}/{}\n", players.online, players.max)),
+                        false => out.push_str(&format!(
+                            "  Players: {}/{} ({})\n",
+                            players.online,
+                            players.max,
+                            names.join(", ")
+                        )),
+                    }
+                }
+                let motd = status.motd();
+                if !motd.is_empty() {
+                    out.push_str(&format!("  MOTD: {}\n", motd.replace('\n', " / ")));
+                }
+            }
+            Err(err) => out.push_str(&format!("{} - offline: {}\n", addr, err)),
+        }
+    }
+
+    out
+}
+
+fn json(results: &[Outcome]) -> String {
+    let servers: Vec<_> = results
+        .iter()
+        .map(|(addr, result)| match result {
+            Ok(status) => json!({
+                "address": addr,
+                "online": true,
+                "latency_ms": latency_ms(status),
+                "status": status,
+            }),
+            Err(err) => json!({
+                "address": addr,
+                "online": false,
+                "error": err.to_string(),
+            }),
+        })
+        .collect();
+
+    format!("{}\n", serde_json::Value::Array(servers))
+}
+
+// Nagios plugin output and the exit code that goes with it
+fn nagios(results: &[Outcome], warning: Option<u64>, critical: Option<u64>) -> (String, u8) {
+    let mut code = NAGIOS_OK;
+    let mut problems = Vec::new();
+    let mut perfdata = Vec::new();
+
+    for (addr, result) in results {
+        match result {
+            Ok(status) => {
+                let ms = latency_ms(status);
+
+                let state = match ms {
+                    Some(ms) if critical.is_some_and(|c| ms > c) => NAGIOS_CRITICAL,
+                    Some(ms) if warning.is_some_and(|w| ms > w) => NAGIOS_WARNING,
+                    _ => NAGIOS_OK,
+                };
+                if state != NAGIOS_OK {
+                    problems.push(format!("{} slow ({} ms)", addr, ms.unwrap_or_default()));
+                }
+                code = code.max(state);
+
+                if let Some(players) = &status.players {
+                    perfdata.push(format!(
+                        "'{}_players'={};;;0;{}",
+                        addr, players.online, players.max
+                    ));
+                }
+                if let Some(ms) = ms {
+                    perfdata.push(format!(
+                        "'{}_latency'={}ms;{};{};0;",
+                        addr,
+                        ms,
+                        warning.map(|w| w.to_string()).unwrap_or_default(),
+                        critical.map(|c| c.to_string()).unwrap_or_default()
+                    ));
+                }
+            }
+            Err(err) => {
+                problems.push(format!("{} offline ({})", addr, err));
+                code = NAGIOS_CRITICAL;
+            }
+        }
+    }
+
+    let label = match code {
+        NAGIOS_OK => "OK",
+        NAGIOS_WARNING => "WARNING",
+        _ => "CRITICAL",
+    };
+    let online = results.iter().filter(|(_, r)| r.is_ok()).count();
+
+    let mut summary = format!(
+        "MINECRAFT {} - {}/{} servers online",
+        label,
+        online,
+        results.len()
+    );
+    if !problems.is_empty() {
+        summary.push_str(": ");
+        summary.push_str(&problems.join(", "));
+    }
+
+    let output = match perfdata.is_empty() {
+        true => format!("{}\n", summary),
+        false => format!("{} | {}\n", summary, perfdata.join(" ")),
+    };
+
+    (output, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn online(addr: &str, players: (i64, i64), latency_ms: u64) -> Outcome {
+        let json = format!(
+            r#"{{"version":{{"name":"1.18.1","protocol":757}},"players":{{"online":{},"max":{},"sample":[{{"name":"Notch"}}]}},"description":"A server"}}"#,
+            players.0, players.1
+        );
+        let mut status: ServerStatus = serde_json::from_str(&json).unwrap();
+        status.latency = Some(Duration::from_millis(latency_ms));
+        (addr.to_string(), Ok(status))
+    }
+
+    fn offline(addr: &str) -> Outcome {
+        (addr.to_string(), Err(PingError::Timeout))
+    }
+
+    #[test]
+    fn nagios_codes_follow_latency_and_outages() {
+        let ok = [online("a", (1, 20), 50)];
+        assert_eq!(nagios(&ok, Some(100), Some(200)).1, NAGIOS_OK);
+        // Without thresholds latency never matters
+        assert_eq!(
+            nagios(&[online("a", (1, 20), 5000)], None, None).1,
+            NAGIOS_OK
+        );
+
+        let (out, code) = nagios(&[online("a", (1, 20), 150)], Some(100), Some(200));
+        assert_eq!(code, NAGIOS_WARNING);
+        assert!(
+            out.starts_with("MINECRAFT WARNING - 1/1 servers online: a slow (150 ms)"),
+            "{}",
+            out
+        );
+
+        let (out, code) = nagios(&[online("a", (1, 20), 250)], Some(100), Some(200));
+        assert_eq!(code, NAGIOS_CRITICAL);
+        assert!(out.starts_with("MINECRAFT CRITICAL"), "{}", out);
+    }
+
+    #[test]
+    fn nagios_is_critical_when_any_server_is_down() {
+        let results = [
+            online("a", (1, 20), 50),
+            offline("b"),
+            online("c", (0, 10), 300),
+        ];
+        let (out, code) = nagios(&results, Some(100), None);
+        assert_eq!(code, NAGIOS_CRITICAL);
+        assert!(
+            out.starts_with("MINECRAFT CRITICAL - 2/3 servers online: b offline (Server took too long to respond), c slow (300 ms)"),
+            "{}",
+            out
+        );
+
+        let (out, code) = nagios(&[offline("a"), offline("b")], None, None);
+        assert_eq!(code, NAGIOS_CRITICAL);
+        assert!(
+            out.starts_with("MINECRAFT CRITICAL - 0/2 servers online"),
+            "{}",
+            out
+        );
+        assert!(!out.contains('|'), "{}", out);
+    }
+
+    #[test]
+    fn nagios_perfdata() {
+        let results = [online("a:25565", (3, 20), 42), offline("b")];
+        let (out, _) = nagios(&results, Some(100), Some(200));
+        let perfdata = out.trim_end().split(" | ").nth(1).unwrap();
+        assert_eq!(
+            perfdata,
+            "'a:25565_players'=3;;;0;20 'a:25565_latency'=42ms;100;200;0;"
+        );
+
+        let (out, _) = nagios(&[online("a", (0, 5), 7)], None, None);
+        assert!(out.ends_with("'a_latency'=7ms;;;0;\n"), "{}", out);
+    }
+
+    #[test]
+    fn text_and_json_fail_when_any_server_is_down() {
+        let up = [online("a", (1, 20), 10), online("b", (2, 20), 10)];
+        assert_eq!(all_online(&up), 0);
+        assert_eq!(all_online(&[online("a", (1, 20), 10), offline("b")]), 1);
+        assert_eq!(all_online(&[offline("a"), offline("b")]), 1);
+    }
+
+    #[test]
+    fn formats_text() {
+        let out = text(&[online("a", (1, 20), 10), offline("b")]);
+        assert_eq!(
+            out,
+            "a - online (10 ms)\n  Version: 1.18.1 (protocol 757)\n  Players: 1/20 (Notch)\n  MOTD: A server\nb - offline: Server took too long to respond\n"
+        );
+    }
+
+    #[test]
+    fn formats_json() {
+        let out = json(&[online("a", (1, 20), 10), offline("b")]);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value[0]["address"], "a");
+        assert_eq!(value[0]["online"], true);
+        assert_eq!(value[0]["latency_ms"], 10);
+        assert_eq!(value[0]["status"]["players"]["online"], 1);
+        assert_eq!(value[1]["online"], false);
+        assert_eq!(value[1]["error"], "Server took too long to respond");
+    }
+
+    #[test]
+    fn refuses_timeouts_a_duration_cant_hold() {
+        assert_eq!(timeout(2.5), Some(Duration::from_millis(2500)));
+        for secs in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e30] {
+            assert_eq!(timeout(secs), None, "{}", secs);
+        }
+    }
+
+    #[test]
+    fn finds_nagios_format_in_unparsed_arguments() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        assert!(wants_nagios(args("mcping --format nagios --bogus")));
+        assert!(wants_nagios(args("mcping -f nagios")));
+        assert!(wants_nagios(args("mcping --format=nagios")));
+        assert!(wants_nagios(args("mcping -fnagios")));
+        assert!(!wants_nagios(args("mcping --format json --bogus")));
+        assert!(!wants_nagios(args("mcping nagios")));
+    }
+}
@@ -0,0 +1,40 @@
+use std::process::Command;
+
+// Runs mcping, returning its exit code and what it printed to stderr
+fn run(args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mcping"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn bad_timeouts_are_unknown_to_nagios() {
+    for timeout in ["0", "-1", "nan", "inf", "1e30"] {
+        let timeout = format!("--timeout={}", timeout);
+        let (code, stderr) = run(&["--format", "nagios", &timeout, "localhost"]);
+        assert_eq!(code, 3, "{}", timeout);
+        assert!(stderr.contains("positive number"), "{}", stderr);
+
+        let (code, _) = run(&[&timeout, "localhost"]);
+        assert_eq!(code, 2, "{}", timeout);
+    }
+}
+
+#[test]
+fn usage_errors_are_unknown_to_nagios() {
+    assert_eq!(run(&["--format", "nagios", "--bogus", "localhost"]).0, 3);
+    assert_eq!(run(&["--format=nagios"]).0, 3);
+    assert_eq!(
+        run(&["-f", "nagios", "--warning", "soon", "localhost"]).0,
+        3
+    );
+
+    assert_eq!(run(&["--bogus", "localhost"]).0, 2);
+    assert_eq!(run(&[]).0, 2);
+    assert_eq!(run(&["--help"]).0, 0);
+}
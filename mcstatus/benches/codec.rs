@@ -0,0 +1,100 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use futures::StreamExt;
+use quartz_nbt::{io, NbtCompound};
+use tokio_util::codec::{Decoder, FramedRead};
+
+use mcstatus::network::{codec::PacketCodec, packets::*, types::*, ServerState};
+
+// Roughly the size of a full chunk section's worth of block data
+const CHUNK_DATA_LEN: usize = 16 * 1024;
+const CHUNKS: usize = 256;
+
+/// Builds one length-prefixed ChunkData packet as the server would send it
+fn chunk_data_frame(x: i32, z: i32) -> Vec<u8> {
+    let mut packet = Packet::new_with_id(0x22);
+    packet.add(&Int(x).to_bytes());
+    packet.add(&Int(z).to_bytes());
+
+    packet.add(&VarInt(1).to_bytes());
+    packet.add(&Long(0xFFFF).to_bytes());
+
+    let mut heightmaps = Vec::new();
+    io::write_nbt(
+        &mut heightmaps,
+        Some(""),
+        &NbtCompound::new(),
+        io::Flavor::Uncompressed,
+    )
+    .unwrap();
+    packet.add(&heightmaps);
+
+    packet.add(&VarInt(1024).to_bytes());
+    for _ in 0..1024 {
+        packet.add(&VarInt(1).to_bytes());
+    }
+
+    packet.add(&VarInt(CHUNK_DATA_LEN as i32).to_bytes());
+    packet.add(&vec![0x5A; CHUNK_DATA_LEN]);
+
+    packet.add(&VarInt(0).to_bytes());
+
+    packet.get_bytes_with_length()
+}
+
+fn chunk_data_stream() -> Vec<u8> {
+    let mut stream = Vec::new();
+    for i in 0..CHUNKS as i32 {
+        stream.extend_from_slice(&chunk_data_frame(i, -i));
+    }
+    stream
+}
+
+fn decode_chunk_data(c: &mut Criterion) {
+    let stream = chunk_data_stream();
+
+    let mut group = c.benchmark_group("chunk_data");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+
+    group.bench_function("decode", |b| {
+        b.iter_batched(
+            || BytesMut::from(stream.as_slice()),
+            |mut buf| {
+                let mut codec = PacketCodec::new(ServerState::Play);
+                let mut chunks = 0;
+                while let Some(packet) = codec.decode(&mut buf).unwrap() {
+                    if let DecodedPacket::ChunkData(_) = packet {
+                        chunks += 1;
+                    }
+                }
+                assert_eq!(chunks, CHUNKS);
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    group.bench_function("framed_read", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let mut framed =
+                    FramedRead::new(stream.as_slice(), PacketCodec::new(ServerState::Play));
+                let mut chunks = 0;
+                while let Some(packet) = framed.next().await {
+                    if let DecodedPacket::ChunkData(_) = packet.unwrap() {
+                        chunks += 1;
+                    }
+                }
+                assert_eq!(chunks, CHUNKS);
+            })
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode_chunk_data);
+criterion_main!(benches);
@@ -0,0 +1,263 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::DEFAULT_PORT;
+
+// Longest a DNS name can be
+const MAX_HOST_LEN: usize = 253;
+
+/// What the host part of an address is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKind {
+    Name,
+    Ipv4,
+    Ipv6,
+}
+
+/// Where to reach a Minecraft server, as typed by a user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerAddress {
+    /// Host name or IP, without brackets around IPv6 addresses
+    pub host: String,
+    pub port: u16,
+    pub kind: HostKind,
+}
+
+/// Why an address couldn't be understood
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    Empty,
+    MissingBracket(String),
+    InvalidIpv6(String),
+    InvalidHost(String),
+    InvalidPort(String),
+    Trailing(String),
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::Empty => write!(f, "The address is empty"),
+            AddressError::MissingBracket(addr) => {
+                write!(f, "`{}` is missing the closing `]`", addr)
+            }
+            AddressError::InvalidIpv6(ip) => write!(f, "`{}` is not an IPv6 address", ip),
+            AddressError::InvalidHost(host) => write!(f, "`{}` is not a valid host name", host),
+            AddressError::InvalidPort(port) => {
+                write!(f, "Port `{}` should be a number from 1 to 65535", port)
+            }
+            AddressError::Trailing(rest) => {
+                write!(f, "Unexpected `{}` after the IPv6 address", rest)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+fn parse_port(port: &str) -> Result<u16, AddressError> {
+    match port.parse() {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(AddressError::InvalidPort(port.to_string())),
+    }
+}
+
+fn valid_host_name(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= MAX_HOST_LEN
+        && host
+            .strip_suffix('.')
+            .unwrap_or(host)
+            .split('.')
+            .all(|label| !label.is_empty())
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+}
+
+impl FromStr for ServerAddress {
+    type Err = AddressError;
+
+    /// Parses `host`, `host:port`, IPv4 with or without a port, `[IPv6]:port`, `[IPv6]` or a bare IPv6 address
+    fn from_str(addr: &str) -> Result<ServerAddress, AddressError> {
+        let addr = addr.trim();
+        if addr.is_empty() {
+            return Err(AddressError::Empty);
+        }
+
+        // Bracketed IPv6, which is the only way to give an IPv6 address a port
+        if let Some(rest) = addr.strip_prefix('[') {
+            let (ip, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| AddressError::MissingBracket(addr.to_string()))?;
+
+            let ip: Ipv6Addr = ip
+                .parse()
+                .map_err(|_| AddressError::InvalidIpv6(ip.to_string()))?;
+
+            let port = match rest {
+                "" => DEFAULT_PORT,
+                _ => match rest.strip_prefix(':') {
+                    Some(port) => parse_port(port)?,
+                    None => return Err(AddressError::Trailing(rest.to_string())),
+                },
+            };
+
+            return Ok(ServerAddress {
+                host: ip.to_string(),
+                port,
+                kind: HostKind::Ipv6,
+            });
+        }
+
+        // More than one colon can only be a bare IPv6 address
+        if addr.matches(':').count() > 1 {
+            let ip: Ipv6Addr = addr
+                .parse()
+                .map_err(|_| AddressError::InvalidIpv6(addr.to_string()))?;
+
+            return Ok(ServerAddress {
+                host: ip.to_string(),
+                port: DEFAULT_PORT,
+                kind: HostKind::Ipv6,
+            });
+        }
+
+        let (host, port) = match addr.split_once(':') {
+            Some((host, port)) => (host, parse_port(port)?),
+            None => (addr, DEFAULT_PORT),
+        };
+
+        let kind = if host.parse::<Ipv4Addr>().is_ok() {
+            HostKind::Ipv4
+        } else if valid_host_name(host) {
+            HostKind::Name
+        } else {
+            return Err(AddressError::InvalidHost(host.to_string()));
+        };
+
+        Ok(ServerAddress {
+            host: host.to_lowercase(),
+            port,
+            kind,
+        })
+    }
+}
+
+impl Display for ServerAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            HostKind::Ipv6 => write!(f, "[{}]:{}", self.host, self.port),
+            _ => write!(f, "{}:{}", self.host, self.port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(addr: &str) -> ServerAddress {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn host_name_gets_default_port() {
+        let addr = parse("mc.example.com");
+        assert_eq!(addr.host, "mc.example.com");
+        assert_eq!(addr.port, DEFAULT_PORT);
+        assert_eq!(addr.kind, HostKind::Name);
+        assert_eq!(addr.to_string(), "mc.example.com:25565");
+    }
+
+    #[test]
+    fn host_name_with_port() {
+        let addr = parse("Play.Example.net:25566");
+        assert_eq!(addr.host, "play.example.net");
+        assert_eq!(addr.port, 25566);
+        assert_eq!(addr.kind, HostKind::Name);
+    }
+
+    #[test]
+    fn ipv4_with_and_without_port() {
+        let addr = parse("192.168.1.10");
+        assert_eq!(addr.kind, HostKind::Ipv4);
+        assert_eq!(addr.to_string(), "192.168.1.10:25565");
+
+        let addr = parse("192.168.1.10:1234");
+        assert_eq!(addr.kind, HostKind::Ipv4);
+        assert_eq!(addr.port, 1234);
+    }
+
+    #[test]
+    fn bare_ipv6_gets_default_port() {
+        let addr = parse("2001:db8::1");
+        assert_eq!(addr.host, "2001:db8::1");
+        assert_eq!(addr.port, DEFAULT_PORT);
+        assert_eq!(addr.kind, HostKind::Ipv6);
+        assert_eq!(addr.to_string(), "[2001:db8::1]:25565");
+    }
+
+    #[test]
+    fn bracketed_ipv6_with_and_without_port() {
+        let addr = parse("[::1]:25565");
+        assert_eq!(addr.host, "::1");
+        assert_eq!(addr.port, 25565);
+        assert_eq!(addr.kind, HostKind::Ipv6);
+
+        let addr = parse("[2001:DB8:0::1]");
+        assert_eq!(addr.host, "2001:db8::1");
+        assert_eq!(addr.port, DEFAULT_PORT);
+    }
+
+    #[test]
+    fn rejects_bad_ports() {
+        for addr in [
+            "mc.example.com:0",
+            "mc.example.com:65536",
+            "1.2.3.4:",
+            "[::1]:port",
+        ] {
+            assert!(
+                matches!(
+                    addr.parse::<ServerAddress>(),
+                    Err(AddressError::InvalidPort(_))
+                ),
+                "{}",
+                addr
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        assert_eq!("".parse::<ServerAddress>(), Err(AddressError::Empty));
+        assert!(matches!(
+            "[::1".parse::<ServerAddress>(),
+            Err(AddressError::MissingBracket(_))
+        ));
+        assert!(matches!(
+            "[mc.example.com]:25565".parse::<ServerAddress>(),
+            Err(AddressError::InvalidIpv6(_))
+        ));
+        assert!(matches!(
+            "2001:db8::1:25565:x".parse::<ServerAddress>(),
+            Err(AddressError::InvalidIpv6(_))
+        ));
+        assert!(matches!(
+            "[::1]25565".parse::<ServerAddress>(),
+            Err(AddressError::Trailing(_))
+        ));
+        assert!(matches!(
+            "mc example.com".parse::<ServerAddress>(),
+            Err(AddressError::InvalidHost(_))
+        ));
+        assert!(matches!(
+            "mc..example.com".parse::<ServerAddress>(),
+            Err(AddressError::InvalidHost(_))
+        ));
+    }
+}
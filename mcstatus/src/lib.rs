@@ -0,0 +1,17 @@
+//! Minecraft protocol types and a client for querying the status of Minecraft servers
+
+mod address;
+pub mod network;
+mod ping;
+
+pub use address::{AddressError, HostKind, ServerAddress};
+
+pub use network::{
+    packets::{DecodedPacket, PacketDecoder},
+    types, ServerState,
+};
+pub use ping::{
+    connect, ping, ping_resolved, ping_stream, ping_with, resolve, resolve_all, ForgeData,
+    ForgeMod, Mod, ModInfo, ModInfoMod, PingError, PingOptions, Player, Players, ServerStatus,
+    Version, DEFAULT_PORT,
+};
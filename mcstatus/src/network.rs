@@ -0,0 +1,447 @@
+pub mod codec;
+pub mod packets;
+use futures::{Sink, SinkExt, StreamExt};
+use log::{error, info};
+use packets::*;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    sync::{broadcast, mpsc},
+};
+use tokio_util::codec::{Framed, FramedRead, FramedWrite};
+
+pub mod types;
+
+use std::{
+    error::Error,
+    io,
+    sync::{Arc, Weak},
+};
+
+use self::{codec::PacketCodec, types::*};
+
+// Sent in status handshakes when the client doesn't care which version the server runs
+pub const PROTOCOL_UNKNOWN: VarInt = VarInt(-1);
+pub const PROTOCOL_1_17_1: VarInt = VarInt(756);
+pub const PROTOCOL_1_18_1: VarInt = VarInt(757);
+
+#[derive(Debug, Clone, Copy)]
+pub enum ServerState {
+    Status,
+    Login,
+    Play,
+}
+
+impl ServerState {
+    /// Longest packet a server may send in this state, in bytes
+    ///
+    /// Status only ever carries a single string of at most 32767 characters, each up to 3 bytes long.
+    /// Login and Play may go up to the largest length the vanilla client accepts, a 3 byte VarInt.
+    pub fn max_packet_len(&self) -> usize {
+        match self {
+            ServerState::Status => 1 << 17,
+            ServerState::Login | ServerState::Play => (1 << 21) - 1,
+        }
+    }
+}
+
+/// Queries the status of a server over an already open connection
+///
+/// # Arguments
+///
+/// * `stream` - Any transport to the server, such as a TcpStream, a proxied or TLS stream, or an in-memory duplex
+/// * `origin` - The host name or address used to reach the server, sent to it in the handshake
+pub async fn status<S>(
+    stream: &mut S,
+    origin: &str,
+) -> Result<StatusResponse, Box<dyn Error + Send + Sync>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(stream, PacketCodec::new(ServerState::Status));
+
+    // Construct and send handshake and login packets
+    send_status_request(&mut framed, origin, PROTOCOL_UNKNOWN).await?;
+    info!("Sent handshake, status request and ping");
+
+    while let Some(packet) = framed.next().await {
+        match packet? {
+            DecodedPacket::StatusResponse(response) => {
+                return Ok(response);
+            }
+            DecodedPacket::Empty => {
+                info!("No response");
+            }
+            DecodedPacket::StatusPong(_payload) => {
+                info!("Got pong");
+            }
+            _ => {
+                break;
+            }
+        }
+    }
+
+    Err("Server did not send a status response".into())
+}
+
+/// Capacity of the command queue and packet broadcast of a managed connection
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Handle to a connection manager task that owns the connection to a server
+///
+/// Commands are queued to the manager, and every packet it receives from the server is broadcast
+/// to all subscribers, so multiple features can share one long-lived connection.
+/// Cloning the handle shares the same connection.
+#[derive(Clone)]
+pub struct NetworkChannel {
+    pub send: mpsc::Sender<NetworkCommand>,
+    // Only the manager holds the sender itself, so subscribers see the channel close with the connection
+    packets: Weak<broadcast::Sender<Arc<DecodedPacket>>>,
+}
+
+impl NetworkChannel {
+    /// Spawns a connection manager task which takes ownership of the stream
+    ///
+    /// `origin` is the host name or address used to reach the server, sent to it in handshakes.
+    pub fn spawn<S>(stream: S, origin: String) -> NetworkChannel
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (send, commands) = mpsc::channel(CHANNEL_CAPACITY);
+        let (packets, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let packets = Arc::new(packets);
+        let channel = NetworkChannel {
+            send,
+            packets: Arc::downgrade(&packets),
+        };
+
+        tokio::spawn(manage_connection(stream, origin, commands, packets));
+
+        channel
+    }
+
+    /// Subscribes to the packets received from the server from this point onwards
+    ///
+    /// The receiver returns `RecvError::Closed` once the connection has been closed.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<DecodedPacket>> {
+        match self.packets.upgrade() {
+            Some(packets) => packets.subscribe(),
+            // Already disconnected, hand out a receiver that is closed from the start
+            None => broadcast::channel(1).1,
+        }
+    }
+
+    /// Queues a command for the connection manager
+    pub async fn command(
+        &self,
+        command: NetworkCommand,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.send.send(command).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to communicate with network commander: {:?}", e);
+                Err("Connection manager is no longer running".into())
+            }
+        }
+    }
+
+    pub async fn send_packet(
+        &self,
+        packet: DecodedPacket,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.command(NetworkCommand::SendPacket(packet)).await
+    }
+
+    pub async fn status(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.command(NetworkCommand::Status).await
+    }
+
+    pub async fn login(
+        &self,
+        protocol: VarInt,
+        port: Short,
+        name: MCString,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.command(NetworkCommand::Login(protocol, port, name))
+            .await
+    }
+
+    pub async fn disconnect(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.command(NetworkCommand::Disconnect).await
+    }
+}
+
+// Types of Messages that can be sent to the connection manager
+#[derive(Debug)]
+pub enum NetworkCommand {
+    Disconnect,
+    // Login(protocol, port, name)
+    Login(VarInt, Short, MCString),
+    Status,
+
+    SendPacket(DecodedPacket),
+}
+
+/// Owns the connection to a server, executing commands and broadcasting received packets
+async fn manage_connection<S>(
+    stream: S,
+    origin: String,
+    mut commands: mpsc::Receiver<NetworkCommand>,
+    packets: Arc<broadcast::Sender<Arc<DecodedPacket>>>,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = tokio::io::split(stream);
+    let mut read = FramedRead::new(read, PacketCodec::new(ServerState::Status));
+    let mut write = FramedWrite::new(write, PacketCodec::new(ServerState::Status));
+
+    loop {
+        // Both branches are cancel safe, so a command arriving never interrupts a partially read packet
+        tokio::select! {
+            command = commands.recv() => {
+                let result = match command {
+                    // Every handle has been dropped or asked to disconnect
+                    None | Some(NetworkCommand::Disconnect) => break,
+                    Some(NetworkCommand::Status) => {
+                        read.decoder_mut().set_state(ServerState::Status);
+                        send_status_request(&mut write, &origin, PROTOCOL_UNKNOWN).await
+                    }
+                    Some(NetworkCommand::Login(protocol_version, port, name)) => {
+                        read.decoder_mut().set_state(ServerState::Login);
+                        send_login(&mut write, &origin, protocol_version, port, name).await
+                    }
+                    Some(NetworkCommand::SendPacket(packet)) => {
+                        write.send(packet).await.map_err(|e| e.into())
+                    }
+                };
+
+                if let Err(e) = result {
+                    error!("Failed to send to server: {}", e);
+                    break;
+                }
+            }
+            packet = read.next() => {
+                let packet = match packet {
+                    Some(Ok(packet)) => packet,
+                    Some(Err(e)) => {
+                        error!("Failed reading packet from stream: {}", e);
+                        break;
+                    }
+                    None => {
+                        info!("Server closed the connection");
+                        break;
+                    }
+                };
+
+                if let DecodedPacket::LoginSuccess(_) = packet {
+                    read.decoder_mut().set_state(ServerState::Play);
+                }
+
+                // Nobody listening is not an error, the packet is just dropped
+                let _ = packets.send(Arc::new(packet));
+            }
+        }
+    }
+
+    if let Err(e) = write.get_mut().shutdown().await {
+        info!("Failed to shut down connection cleanly: {}", e);
+    }
+}
+
+/// Sends the handshake, status request and ping used to query a server's status
+pub(crate) async fn send_status_request<S>(
+    sink: &mut S,
+    origin: &str,
+    protocol_version: VarInt,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    S: Sink<DecodedPacket, Error = io::Error> + Unpin,
+{
+    let handshake = DecodedPacket::Handshake(Handshake {
+        protocol_version,
+        origin: MCString(origin.to_string()),
+        port: Short(0),
+        next_state: HandshakeMode::Status,
+    });
+
+    // Buffer all three and write them out together
+    sink.feed(handshake).await?;
+    sink.feed(DecodedPacket::StatusRequest(StatusRequest {}))
+        .await?;
+    sink.feed(DecodedPacket::StatusPing(StatusPing {})).await?;
+    sink.flush().await?;
+
+    Ok(())
+}
+
+/// Sends the handshake and login start packets to begin logging in to a server
+async fn send_login<S>(
+    sink: &mut S,
+    origin: &str,
+    protocol_version: VarInt,
+    port: Short,
+    name: MCString,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    S: Sink<DecodedPacket, Error = io::Error> + Unpin,
+{
+    let handshake = DecodedPacket::Handshake(Handshake {
+        protocol_version,
+        origin: MCString(origin.to_string()),
+        port,
+        next_state: HandshakeMode::Login,
+    });
+
+    sink.feed(handshake).await?;
+    sink.feed(DecodedPacket::LoginStart(name)).await?;
+    sink.flush().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, AsyncReadExt, DuplexStream};
+
+    const RESPONSE: &str = r#"{"version":{"name":"1.18.1","protocol":757},"players":{"max":20,"online":0},"description":{"text":"A Minecraft Server"}}"#;
+
+    /// Reads one serverbound frame off the server's end of the duplex and returns its bytes
+    async fn read_frame(server: &mut DuplexStream) -> Vec<u8> {
+        let VarInt(len) = VarInt::from_stream(server).await.unwrap().unwrap();
+        let mut buf = vec![0; len as usize];
+        server.read_exact(&mut buf).await.unwrap();
+        buf
+    }
+
+    /// Plays the server side of a status query, checking what the client sends
+    async fn answer_status(server: &mut DuplexStream) {
+        let handshake = read_frame(server).await;
+        assert_eq!(handshake[0], Handshake::ID);
+        assert_eq!(
+            *handshake.last().unwrap(),
+            0x01,
+            "handshake should ask for status"
+        );
+
+        let request = read_frame(server).await;
+        assert_eq!(request, vec![StatusRequest::ID]);
+
+        let ping = read_frame(server).await;
+        assert_eq!(ping[0], StatusPing::ID);
+
+        let mut response = Packet::new_with_id(StatusResponse::ID);
+        response.add(&MCString(RESPONSE.to_string()).to_bytes());
+        server
+            .write_all(&response.get_bytes_with_length())
+            .await
+            .unwrap();
+
+        // Echo the ping payload back
+        let mut pong = Packet::new_with_id(StatusPong::ID);
+        pong.add(&ping[1..]);
+        server
+            .write_all(&pong.get_bytes_with_length())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn status_over_duplex() {
+        let (mut client, mut server) = duplex(1024);
+
+        let server = tokio::spawn(async move { answer_status(&mut server).await });
+
+        let response = status(&mut client, "localhost").await.unwrap();
+        assert_eq!(response.response.0, RESPONSE);
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn status_fails_when_server_hangs_up() {
+        let (mut client, server) = duplex(1024);
+        drop(server);
+
+        assert!(status(&mut client, "localhost").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn status_reads_response_split_across_writes() {
+        let (mut client, mut server) = duplex(1024);
+
+        let server = tokio::spawn(async move {
+            for _ in 0..3 {
+                read_frame(&mut server).await;
+            }
+
+            let mut response = Packet::new_with_id(StatusResponse::ID);
+            response.add(&MCString(RESPONSE.to_string()).to_bytes());
+
+            // Dribble the response out a few bytes at a time
+            for chunk in response.get_bytes_with_length().chunks(7) {
+                server.write_all(chunk).await.unwrap();
+                server.flush().await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let response = status(&mut client, "localhost").await.unwrap();
+        assert_eq!(response.response.0, RESPONSE);
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn channel_broadcasts_to_every_subscriber() {
+        let (client, mut server) = duplex(1024);
+
+        let channel = NetworkChannel::spawn(client, "localhost".to_string());
+        let mut first = channel.subscribe();
+        let mut second = channel.subscribe();
+
+        channel.status().await.unwrap();
+        answer_status(&mut server).await;
+
+        for subscriber in [&mut first, &mut second] {
+            match &*subscriber.recv().await.unwrap() {
+                DecodedPacket::StatusResponse(response) => {
+                    assert_eq!(response.response.0, RESPONSE)
+                }
+                other => panic!("Expected status response, got {:?}", other),
+            }
+            match &*subscriber.recv().await.unwrap() {
+                DecodedPacket::StatusPong(_) => {}
+                other => panic!("Expected pong, got {:?}", other),
+            }
+        }
+
+        channel.disconnect().await.unwrap();
+        assert!(first.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn channel_sends_packets_to_server() {
+        let (client, mut server) = duplex(1024);
+
+        let channel = NetworkChannel::spawn(client, "localhost".to_string());
+        channel
+            .send_packet(DecodedPacket::KeepAliveServerbound(Long(42)))
+            .await
+            .unwrap();
+
+        let frame = read_frame(&mut server).await;
+        assert_eq!(frame[0], 0x0f);
+        assert_eq!(&frame[1..], &Long(42).to_bytes()[..]);
+    }
+
+    #[tokio::test]
+    async fn channel_closes_when_server_hangs_up() {
+        let (client, server) = duplex(1024);
+
+        let channel = NetworkChannel::spawn(client, "localhost".to_string());
+        let mut packets = channel.subscribe();
+        drop(server);
+
+        assert!(packets.recv().await.is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{packets::*, types::*, ServerState};
+
+/// Most bytes a VarInt can take up on the wire
+const MAX_VARINT_LEN: usize = 5;
+
+/// Frames the length-prefixed packets of the Minecraft protocol
+///
+/// Incoming frames are decoded according to the current server state, which must be kept
+/// up to date with `set_state` as the connection moves between Status, Login and Play.
+/// Wrap a stream in a `Framed` (or `FramedRead`/`FramedWrite`) to get buffered reads and writes.
+#[derive(Debug)]
+pub struct PacketCodec {
+    state: ServerState,
+}
+
+impl PacketCodec {
+    pub fn new(state: ServerState) -> PacketCodec {
+        PacketCodec { state }
+    }
+
+    pub fn state(&self) -> ServerState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: ServerState) {
+        self.state = state;
+    }
+}
+
+/// Reads a VarInt from the start of a buffer without consuming it
+///
+/// # Returns
+///
+/// * `Ok(Some((VarInt, usize)))` with the value and how many bytes it took up
+/// * `Ok(None)` if the buffer ends before the VarInt does
+/// * `Err(e)` if the VarInt is longer than 5 bytes
+fn peek_varint(buf: &[u8]) -> Result<Option<(VarInt, usize)>, io::Error> {
+    const PART: u32 = 0x7F;
+    let mut val = 0u32;
+
+    for (i, byte) in buf.iter().take(MAX_VARINT_LEN).enumerate() {
+        val |= (*byte as u32 & PART) << (i * 7);
+        if (byte & 0x80) == 0 {
+            return Ok(Some((VarInt(val as i32), i + 1)));
+        }
+    }
+
+    if buf.len() >= MAX_VARINT_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "VarInt too big!",
+        ));
+    }
+
+    Ok(None)
+}
+
+impl Decoder for PacketCodec {
+    type Item = DecodedPacket;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<DecodedPacket>, io::Error> {
+        let (VarInt(len), header) = match peek_varint(src)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+
+        if len < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Negative packet length: {}", len),
+            ));
+        }
+        let len = len as usize;
+
+        // Refuse before making room for it, so a server can't make us allocate whatever it likes
+        if len > self.state.max_packet_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Packet length {} exceeds the {} bytes allowed in {:?}",
+                    len,
+                    self.state.max_packet_len(),
+                    self.state
+                ),
+            ));
+        }
+
+        // Wait for the rest of the packet, making room for it in one go
+        if src.len() < header + len {
+            src.reserve(header + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(header);
+        let frame = src.split_to(len);
+
+        // Return packet without decompressing
+        decode_packet(&frame, &self.state).map(Some)
+    }
+}
+
+impl Encoder<DecodedPacket> for PacketCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: DecodedPacket, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let packet = match item.encode() {
+            Some(packet) => packet,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Failed to encode packet: {:?}", item),
+                ))
+            }
+        };
+
+        // Send without compression
+        let len = VarInt(packet.size() as i32);
+        dst.reserve(len.num_bytes() + packet.size());
+        dst.put_slice(&len.to_bytes());
+        dst.put_slice(packet.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prefixes a packet with its length, the way it arrives on the wire
+    fn frame(packet: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::from(&VarInt(packet.len() as i32).to_bytes()[..]);
+        buf.put_slice(packet);
+        buf
+    }
+
+    fn decode(state: ServerState, buf: &mut BytesMut) -> Result<Option<DecodedPacket>, io::Error> {
+        PacketCodec::new(state).decode(buf)
+    }
+
+    #[test]
+    fn refuses_packets_over_the_state_limit() {
+        // Only the length has arrived, which is all it takes to refuse a Status packet
+        let len = ServerState::Status.max_packet_len() as i32 + 1;
+        let mut buf = BytesMut::from(&VarInt(len).to_bytes()[..]);
+        assert!(decode(ServerState::Status, &mut buf).is_err());
+
+        // Play packets may be that long, so wait for the rest
+        assert!(matches!(decode(ServerState::Play, &mut buf), Ok(None)));
+
+        let mut buf = BytesMut::from(&VarInt(i32::MAX).to_bytes()[..]);
+        assert!(decode(ServerState::Play, &mut buf).is_err());
+
+        let mut buf = BytesMut::from(&VarInt(-1).to_bytes()[..]);
+        assert!(decode(ServerState::Status, &mut buf).is_err());
+    }
+
+    #[test]
+    fn refuses_strings_longer_than_the_packet() {
+        let mut packet = vec![StatusResponse::ID];
+        packet.extend(VarInt(1000).to_bytes());
+        packet.extend(b"{}");
+        let error = decode(ServerState::Status, &mut frame(&packet)).unwrap_err();
+        assert!(error.to_string().contains("1000"), "{}", error);
+
+        let mut packet = vec![StatusResponse::ID];
+        packet.extend(VarInt(-2).to_bytes());
+        packet.extend(b"{}");
+        assert!(decode(ServerState::Status, &mut frame(&packet)).is_err());
+
+        // The length itself is cut off
+        assert!(decode(ServerState::Status, &mut frame(&[StatusResponse::ID])).is_err());
+    }
+
+    #[test]
+    fn refuses_arrays_longer_than_the_packet() {
+        // Encryption request with an empty server ID and an enormous public key
+        let mut packet = vec![EncryptionRequest::ID, 0x00];
+        packet.extend(VarInt(i32::MAX).to_bytes());
+        assert!(decode(ServerState::Login, &mut frame(&packet)).is_err());
+
+        // Chunk data claiming more bit mask longs than there are bytes for
+        let mut packet = vec![ChunkData::ID];
+        packet.extend([0; 8]);
+        packet.extend(VarInt(1 << 20).to_bytes());
+        packet.extend([0; 16]);
+        assert!(decode(ServerState::Play, &mut frame(&packet)).is_err());
+    }
+
+    #[test]
+    fn refuses_truncated_fixed_size_fields() {
+        // A pong with one byte of its Long payload
+        let error = decode(ServerState::Status, &mut frame(&[StatusPong::ID, 0x01])).unwrap_err();
+        assert!(error.to_string().contains("8 byte"), "{}", error);
+
+        // Unload chunk with a whole X but only half of Z
+        let mut packet = vec![UnloadChunk::ID];
+        packet.extend([0; 4 + 2]);
+        assert!(decode(ServerState::Play, &mut frame(&packet)).is_err());
+
+        // Keep alive with no ID at all
+        let packet = [KeepAliveClientbound::ID];
+        assert!(decode(ServerState::Play, &mut frame(&packet)).is_err());
+
+        // The same packets decode when whole
+        let mut packet = vec![StatusPong::ID];
+        packet.extend(Long(7).to_bytes());
+        match decode(ServerState::Status, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::StatusPong(pong))) => assert_eq!(pong.payload.0, 7),
+            other => panic!("Expected pong, got {:?}", other),
+        }
+
+        let mut packet = vec![UnloadChunk::ID];
+        packet.extend(Int(-3).to_bytes());
+        packet.extend(Int(4).to_bytes());
+        match decode(ServerState::Play, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::UnloadChunk(unload))) => {
+                assert_eq!((unload.x.0, unload.z.0), (-3, 4))
+            }
+            other => panic!("Expected unload chunk, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refuses_counts_that_dont_fit_in_play_packets() {
+        // Each packet with nothing but its count, after the fields that come first
+        let prefixes: [(u8, &[u8]); 6] = [
+            (Statistics::ID, &[]),
+            (TabComplete::ID, &[0x01, 0x00, 0x00]),
+            (Explosion::ID, &[0; 16]),
+            (JoinGame::ID, &[0, 0, 0, 1, 0x00, 0x01, 0x00]),
+            (DestroyEntities::ID, &[]),
+            (EntityProperties::ID, &[0x01]),
+        ];
+
+        for (id, prefix) in prefixes {
+            for count in [-1, i32::MAX, 1 << 20] {
+                let mut packet = vec![id];
+                packet.extend(prefix);
+                packet.extend(VarInt(count).to_bytes());
+                let error = decode(ServerState::Play, &mut frame(&packet)).unwrap_err();
+                assert!(
+                    error.to_string().contains("Length"),
+                    "{:02x}: {}",
+                    id,
+                    error
+                );
+            }
+        }
+
+        // The frame from the report: destroy entities with a count of -1
+        let packet = [DestroyEntities::ID, 0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(decode(ServerState::Play, &mut frame(&packet)).is_err());
+
+        // Modifiers are counted the same way, inside each property
+        let mut packet = vec![EntityProperties::ID, 0x01, 0x01];
+        packet.extend(MCString(String::from("generic.speed")).to_bytes());
+        packet.extend(Double(0.1).to_bytes());
+        packet.extend(VarInt(i32::MAX).to_bytes());
+        assert!(decode(ServerState::Play, &mut frame(&packet)).is_err());
+
+        // Window items aren't decoded from the wire yet, but their slots are counted too
+        let mut packet = vec![WindowItems::ID, 0x00, 0x01];
+        packet.extend(VarInt(i32::MAX).to_bytes());
+        let mut pd = PacketDecoder::new(&packet, 1);
+        let items = WindowItems::decode(&mut pd);
+        assert!(items.slots.is_empty());
+        assert!(pd.error().is_some());
+    }
+
+    #[test]
+    fn refuses_truncated_varints() {
+        // A continuation bit with nothing after it
+        for packet in [
+            vec![DestroyEntities::ID, 0x02, 0x05, 0x80],
+            vec![Statistics::ID, 0x01, 0x01, 0x02, 0xff],
+            vec![EntityProperties::ID, 0x80],
+        ] {
+            assert!(
+                decode(ServerState::Play, &mut frame(&packet)).is_err(),
+                "{:02x?}",
+                packet
+            );
+        }
+
+        // Whole VarInts still decode
+        let packet = [DestroyEntities::ID, 0x02, 0x05, 0x80, 0x01];
+        match decode(ServerState::Play, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::DestroyEntities(destroy))) => {
+                let ids: Vec<i32> = destroy.entities.iter().map(|id| id.0).collect();
+                assert_eq!(ids, [5, 128]);
+            }
+            other => panic!("Expected destroy entities, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_slots() {
+        // Window 0, state 1, slot 36, nothing in it
+        let mut packet = vec![SetSlot::ID, 0x00, 0x01];
+        packet.extend(Short(36).to_bytes());
+        packet.push(0x00);
+        match decode(ServerState::Play, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::SetSlot(set))) => {
+                assert_eq!(set.slot_id.0, 36);
+                assert!(!set.slot_data.0 .0);
+            }
+            other => panic!("Expected set slot, got {:?}", other),
+        }
+
+        // Item 1, count 64, no NBT
+        let mut packet = vec![SetSlot::ID, 0x00, 0x01];
+        packet.extend(Short(36).to_bytes());
+        packet.extend([0x01, 0x01, 0x40, 0x00]);
+        match decode(ServerState::Play, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::SetSlot(set))) => {
+                assert_eq!(set.slot_data.1.map(|id| id.0), Some(1));
+                assert_eq!(set.slot_data.2.map(|count| count.0), Some(64));
+                assert!(set.slot_data.3.is_none());
+            }
+            other => panic!("Expected set slot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn passes_on_packets_without_decoders() {
+        // Boss bar, declare commands, plugin message, light, map and trade list
+        for id in [0x0d, 0x12, 0x18, 0x25, 0x27, 0x28] {
+            match decode(ServerState::Play, &mut frame(&[id, 0x01, 0x02])) {
+                Ok(Some(DecodedPacket::Unknown(bytes))) => assert_eq!(bytes, [id, 0x01, 0x02]),
+                other => panic!("Expected {:02x} to be unknown, got {:?}", id, other),
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_arrays_that_fit() {
+        let mut packet = vec![EncryptionRequest::ID];
+        packet.extend(MCString(String::from("server")).to_bytes());
+        packet.extend([0x02, 0x0A, 0x0B, 0x01, 0x0C]);
+
+        match decode(ServerState::Login, &mut frame(&packet)) {
+            Ok(Some(DecodedPacket::EncryptionRequest(request))) => {
+                assert_eq!(request.server_id.0, "server");
+                assert_eq!(request.public_key.len(), 2);
+                assert_eq!(request.verify_token.len(), 1);
+            }
+            other => panic!("Expected encryption request, got {:?}", other),
+        }
+    }
+}
@@ -1,12 +1,12 @@
 #![allow(dead_code)]
 
 use std::{
-    io::{Cursor, Error},
+    io::{Cursor, Error, ErrorKind},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use log::debug;
-use quartz_nbt::io;
+use quartz_nbt::{io, NbtCompound};
 
 use super::{types::*, ServerState};
 
@@ -140,21 +140,21 @@ pub struct EncryptionRequest {
 impl ClientboundPacket for EncryptionRequest {
     fn decode(pd: &mut PacketDecoder) -> Self {
         let server_id = pd.next_string();
-        let public_key_len = pd.next_varint();
+        let public_key_len = pd.next_len(1);
         let mut public_key: Vec<Byte> = Vec::new();
-        for _ in 0..public_key_len.0 {
+        for _ in 0..public_key_len {
             public_key.push(pd.next_byte());
         }
-        let verify_token_len = pd.next_varint();
+        let verify_token_len = pd.next_len(1);
         let mut verify_token: Vec<Byte> = Vec::new();
-        for _ in 0..verify_token_len.0 {
+        for _ in 0..verify_token_len {
             verify_token.push(pd.next_byte());
         }
         EncryptionRequest {
             server_id,
-            public_key_len,
+            public_key_len: VarInt(public_key_len as i32),
             public_key,
-            verify_token_len,
+            verify_token_len: VarInt(verify_token_len as i32),
             verify_token,
         }
     }
@@ -369,11 +369,11 @@ impl ClientboundPacket for SpawnPlayer {
 #[derive(Debug)]
 pub struct SculkVibrationSignal {
     // 0x05
-// TODO
+    // TODO
 }
 
 impl ClientboundPacket for SculkVibrationSignal {
-    fn decode(pd: &mut PacketDecoder) -> Self {
+    fn decode(_pd: &mut PacketDecoder) -> Self {
         SculkVibrationSignal {}
     }
 
@@ -411,13 +411,17 @@ pub struct Statistics {
 
 impl ClientboundPacket for Statistics {
     fn decode(pd: &mut PacketDecoder) -> Self {
-        let stats_len = pd.next_varint();
+        // Three VarInts of at least a byte each
+        let stats_len = pd.next_len(3);
         let mut stats: Vec<(VarInt, VarInt, VarInt)> = Vec::new();
-        for _ in 0..stats_len.0 {
+        for _ in 0..stats_len {
             stats.push((pd.next_varint(), pd.next_varint(), pd.next_varint()));
         }
 
-        Statistics { stats_len, stats }
+        Statistics {
+            stats_len: VarInt(stats_len as i32),
+            stats,
+        }
     }
 
     const ID: u8 = 0x07;
@@ -526,6 +530,7 @@ impl ClientboundPacket for BlockChange {
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct BossBar {
     // 0x0d
     pub uuid: UUID, // UUID for this bar
@@ -533,14 +538,6 @@ pub struct BossBar {
                     // TODO
 }
 
-impl ClientboundPacket for BossBar {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-    }
-
-    const ID: u8 = 0x0d;
-}
-
 #[derive(Debug)]
 pub struct ServerDifficulty {
     // 0x0e
@@ -614,9 +611,10 @@ impl ClientboundPacket for TabComplete {
         let transaction_id = pd.next_varint();
         let start = pd.next_varint();
         let len = pd.next_varint();
-        let matches_len = pd.next_varint();
+        // A string and a Boolean
+        let matches_len = pd.next_len(2);
         let mut matches: Vec<(MCString, Boolean, Option<Chat>)> = Vec::new();
-        for _ in 0..matches_len.0 {
+        for _ in 0..matches_len {
             let str = pd.next_string();
             let boolean = pd.next_bool();
             let present = boolean.0;
@@ -633,7 +631,7 @@ impl ClientboundPacket for TabComplete {
             transaction_id,
             start,
             len,
-            matches_len,
+            matches_len: VarInt(matches_len as i32),
             matches,
         }
     }
@@ -642,17 +640,10 @@ impl ClientboundPacket for TabComplete {
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct DeclareCommands {
     // 0x12
-// TODO
-}
-
-impl ClientboundPacket for DeclareCommands {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-    }
-
-    const ID: u8 = 0x12;
+    // TODO
 }
 
 #[derive(Debug)]
@@ -685,15 +676,16 @@ impl ClientboundPacket for WindowItems {
     fn decode(pd: &mut PacketDecoder) -> Self {
         let window_id = pd.next_ubyte();
         let state_id = pd.next_varint();
-        let slots_len = pd.next_varint();
+        // An empty slot is a single Boolean
+        let slots_len = pd.next_len(1);
         let mut slots: Vec<Slot> = Vec::new();
-        for _ in 0..slots_len.0 {
+        for _ in 0..slots_len {
             slots.push(pd.next_slot());
         }
         WindowItems {
             window_id,
             state_id,
-            slots_len,
+            slots_len: VarInt(slots_len as i32),
             slots,
             carried: pd.next_slot(),
         }
@@ -763,20 +755,13 @@ impl ClientboundPacket for SetCooldown {
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct PluginMessage {
     // 0x18
     pub channel: Identifier, // Name of Plugin Channel used
     pub data: Vec<Byte>,     // Data for that channel
 }
 
-impl ClientboundPacket for PluginMessage {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!("Plugin Message")
-    }
-
-    const ID: u8 = 0x18;
-}
-
 #[derive(Debug)]
 pub struct NamedSoundEffect {
     // 0x19
@@ -864,9 +849,9 @@ impl ClientboundPacket for Explosion {
         let y = pd.next_float();
         let z = pd.next_float();
         let strength = pd.next_float();
-        let blocks_len = pd.next_varint();
+        let blocks_len = pd.next_len(3);
         let mut block_offsets: Vec<(Byte, Byte, Byte)> = Vec::new();
-        for _ in 0..blocks_len.0 {
+        for _ in 0..blocks_len {
             block_offsets.push((pd.next_byte(), pd.next_byte(), pd.next_byte()));
         }
         Explosion {
@@ -874,7 +859,7 @@ impl ClientboundPacket for Explosion {
             y,
             z,
             strength,
-            blocks_len,
+            blocks_len: VarInt(blocks_len as i32),
             block_offsets,
             vx: pd.next_float(),
             vy: pd.next_float(),
@@ -1007,38 +992,39 @@ impl ClientboundPacket for ChunkData {
     fn decode(pd: &mut PacketDecoder) -> Self {
         let chunk_x = pd.next_int();
         let chunk_z = pd.next_int();
-        let bit_mask_len = pd.next_varint();
+        let bit_mask_len = pd.next_len(8);
         let mut bit_mask: Vec<Long> = Vec::new();
-        for _ in 0..bit_mask_len.0 as usize {
+        for _ in 0..bit_mask_len {
             bit_mask.push(pd.next_long());
         }
         let heightmaps = pd.next_nbt();
-        let biomes_len = pd.next_varint();
+        // Every VarInt and NBT tag takes up at least a byte
+        let biomes_len = pd.next_len(1);
         let mut biomes: Vec<VarInt> = Vec::new();
-        for _ in 0..biomes_len.0 as usize {
+        for _ in 0..biomes_len {
             biomes.push(pd.next_varint());
         }
-        let data_len = pd.next_varint();
+        let data_len = pd.next_len(1);
         let mut data = Vec::new();
-        for _ in 0..data_len.0 as usize {
+        for _ in 0..data_len {
             data.push(pd.next_byte().0 as u8);
         }
-        let blocks_len = pd.next_varint();
+        let blocks_len = pd.next_len(1);
         let mut block_entities = Vec::new();
-        for _ in 0..blocks_len.0 as usize {
+        for _ in 0..blocks_len {
             block_entities.push(pd.next_nbt());
         }
         ChunkData {
             x: chunk_x,
             z: chunk_z,
-            bit_mask_len,
+            bit_mask_len: VarInt(bit_mask_len as i32),
             bit_mask,
             heightmaps,
-            biomes_len,
+            biomes_len: VarInt(biomes_len as i32),
             biomes,
-            data_len,
+            data_len: VarInt(data_len as i32),
             data,
-            block_entities_len: blocks_len,
+            block_entities_len: VarInt(blocks_len as i32),
             block_entities,
         }
     }
@@ -1069,6 +1055,7 @@ impl ClientboundPacket for Effect {
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct Particle {
     // 0x24
     pub particle_id: Int,
@@ -1084,27 +1071,8 @@ pub struct Particle {
     // TODO - Data
 }
 
-impl ClientboundPacket for Particle {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-        // Particle {
-        //     particle_id: pd.next_int(),
-        //     long_distance: pd.next_bool(),
-        //     x: pd.next_double(),
-        //     y: pd.next_double(),
-        //     z: pd.next_double(),
-        //     off_x: pd.next_float(),
-        //     off_y: pd.next_float(),
-        //     off_z: pd.next_float(),
-        //     particle_data: pd.next_float(),
-        //     particle_count: pd.next_int(),
-        // }
-    }
-
-    const ID: u8 = 0x24;
-}
-
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct UpdateLight {
     // 0x25
     pub chunk_x: VarInt,
@@ -1124,14 +1092,6 @@ pub struct UpdateLight {
     pub block_lights: Vec<(VarInt, [Byte; 2048])>,
 }
 
-impl ClientboundPacket for UpdateLight {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-    }
-
-    const ID: u8 = 0x25;
-}
-
 #[derive(Debug)]
 pub struct JoinGame {
     // 0x26
@@ -1159,9 +1119,9 @@ impl ClientboundPacket for JoinGame {
         let is_hardcore = pd.next_bool();
         let gamemode = pd.next_ubyte();
         let prev_gamemode = pd.next_byte();
-        let world_names_len = pd.next_varint();
+        let world_names_len = pd.next_len(1);
         let mut world_names: Vec<Identifier> = Vec::new();
-        for _ in 0..world_names_len.0 as usize {
+        for _ in 0..world_names_len {
             world_names.push(pd.next_string());
         }
         JoinGame {
@@ -1169,7 +1129,7 @@ impl ClientboundPacket for JoinGame {
             is_hardcore,
             gamemode,
             prev_gamemode,
-            world_names_len,
+            world_names_len: VarInt(world_names_len as i32),
             world_names,
             dimension_codec: pd.next_nbt(),
             dimension: pd.next_nbt(),
@@ -1188,31 +1148,17 @@ impl ClientboundPacket for JoinGame {
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct MapData {
     // 0x27
-// TODO
-}
-
-impl ClientboundPacket for MapData {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-    }
-
-    const ID: u8 = 0x27;
+    // TODO
 }
 
 #[derive(Debug)]
+// No decoder yet, so it arrives as DecodedPacket::Unknown
 pub struct TradeList {
     // 0x28
-// TODO
-}
-
-impl ClientboundPacket for TradeList {
-    fn decode(pd: &mut PacketDecoder) -> Self {
-        todo!()
-    }
-
-    const ID: u8 = 0x28;
+    // TODO
 }
 
 #[derive(Debug)]
@@ -1328,14 +1274,14 @@ pub struct DestroyEntities {
 
 impl ClientboundPacket for DestroyEntities {
     fn decode(pd: &mut PacketDecoder) -> Self {
-        let vi_num = pd.next_varint();
+        let vi_num = pd.next_len(1);
         let mut ids: Vec<VarInt> = Vec::new();
 
-        for _ in 0..vi_num.0 as usize {
+        for _ in 0..vi_num {
             ids.push(pd.next_varint());
         }
         DestroyEntities {
-            entities_len: vi_num,
+            entities_len: VarInt(vi_num as i32),
             entities: ids,
         }
     }
@@ -1441,11 +1387,11 @@ impl ClientboundPacket for TimeUpdate {
 #[derive(Debug)]
 pub struct SoundEffect {
     // 0x5c
-// TODO - Sound Effect
+    // TODO - Sound Effect
 }
 
 impl ClientboundPacket for SoundEffect {
-    fn decode(pd: &mut PacketDecoder) -> Self {
+    fn decode(_pd: &mut PacketDecoder) -> Self {
         SoundEffect {}
     }
 
@@ -1484,39 +1430,35 @@ impl ClientboundPacket for EntityTeleport {
 pub struct EntityProperties {
     pub entity_id: VarInt,
     pub num_properties: VarInt,
-    pub properties: Vec<(
-        // List of properties
-        Identifier, // Key
-        Double,     // Value
-        VarInt,     // Num of Modifiers
-        Vec<(
-            // List of Modifier Data
-            UUID,   // UUID
-            Double, // Amount
-            Byte,   // Operation
-        )>,
-    )>,
+    pub properties: Vec<EntityProperty>,
 }
 
+// Key, Value, Num of Modifiers, List of Modifier Data
+pub type EntityProperty = (Identifier, Double, VarInt, Vec<PropertyModifier>);
+
+// UUID, Amount, Operation
+pub type PropertyModifier = (UUID, Double, Byte);
+
 impl ClientboundPacket for EntityProperties {
     fn decode(pd: &mut PacketDecoder) -> Self {
         let entity_id = pd.next_varint();
-        let num_properties = pd.next_varint();
-        let mut properties: Vec<(Identifier, Double, VarInt, Vec<(UUID, Double, Byte)>)> =
-            Vec::new();
-        for _ in 0..num_properties.0 {
+        // A key, a Double and a modifier count
+        let num_properties = pd.next_len(1 + 8 + 1);
+        let mut properties: Vec<EntityProperty> = Vec::new();
+        for _ in 0..num_properties {
             let iden = pd.next_string();
             let doub = pd.next_double();
-            let num_modifiers = pd.next_varint();
-            let mut modifiers: Vec<(UUID, Double, Byte)> = Vec::new();
-            for _ in 0..num_modifiers.0 {
+            // A UUID, a Double and a Byte
+            let num_modifiers = pd.next_len(16 + 8 + 1);
+            let mut modifiers: Vec<PropertyModifier> = Vec::new();
+            for _ in 0..num_modifiers {
                 modifiers.push((pd.next_uuid(), pd.next_double(), pd.next_byte()));
             }
-            properties.push((iden, doub, num_modifiers, modifiers));
+            properties.push((iden, doub, VarInt(num_modifiers as i32), modifiers));
         }
         EntityProperties {
             entity_id,
-            num_properties,
+            num_properties: VarInt(num_properties as i32),
             properties,
         }
     }
@@ -1720,6 +1662,7 @@ impl DecodedPacket {
 }
 
 // A raw collection of bytes used to contruct a packet
+#[derive(Default)]
 pub struct Packet {
     bytes: Vec<u8>,
 }
@@ -1736,10 +1679,8 @@ impl Packet {
     }
 
     /// Push a vector of bytes to the packet
-    pub fn add(&mut self, bytes: &Vec<u8>) {
-        for b in bytes.iter() {
-            self.bytes.push(*b);
-        }
+    pub fn add(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
     }
 
     /// Push a single byte to the packet
@@ -1750,17 +1691,20 @@ impl Packet {
     /// Returns the packet as a vector of bytes with the length signed as a VarInt at the start
     pub fn get_bytes_with_length(&self) -> Vec<u8> {
         let len = VarInt(self.bytes.len() as i32);
-        let mut bytes: Vec<u8> = Vec::new();
-        bytes.append(&mut len.to_bytes());
-        bytes.append(&mut self.bytes.clone());
+        let mut bytes: Vec<u8> = Vec::with_capacity(len.num_bytes() + self.bytes.len());
+        bytes.extend_from_slice(&len.to_bytes());
+        bytes.extend_from_slice(&self.bytes);
         bytes
     }
 
     /// Returns the packet as a vector of bytes with no size signature at the start
     pub fn get_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.append(&mut self.bytes.clone());
-        bytes
+        self.bytes.clone()
+    }
+
+    /// Borrows the bytes of the packet with no size signature at the start
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
     }
 
     pub fn size(&self) -> usize {
@@ -1769,16 +1713,16 @@ impl Packet {
 }
 
 /// Decodes a packet from a vector of bytes into a DecodedPacket, given the server state
-pub fn decode_packet(packet: Vec<u8>, state: &ServerState) -> DecodedPacket {
+pub fn decode_packet(packet: &[u8], state: &ServerState) -> Result<DecodedPacket, Error> {
     // use DecodedPacket::*;
 
-    if packet.len() == 0 {
-        return DecodedPacket::Empty;
+    if packet.is_empty() {
+        return Ok(DecodedPacket::Empty);
     }
 
     let out: DecodedPacket;
 
-    let mut pd = PacketDecoder::new(&packet, 1);
+    let mut pd = PacketDecoder::new(packet, 1);
 
     match packet[0] {
         0x00 => match state {
@@ -1802,21 +1746,21 @@ pub fn decode_packet(packet: Vec<u8>, state: &ServerState) -> DecodedPacket {
             ServerState::Play => {
                 out = DecodedPacket::SpawnLivingEntity(SpawnLivingEntity::decode(&mut pd))
             }
-            _ => out = DecodedPacket::Unknown(packet),
+            _ => out = DecodedPacket::Unknown(packet.to_vec()),
         },
         0x03 => match state {
             ServerState::Login => {
                 out = DecodedPacket::SetCompression(SetCompression::decode(&mut pd))
             }
             ServerState::Play => out = DecodedPacket::SpawnPainting(SpawnPainting::decode(&mut pd)),
-            _ => out = DecodedPacket::Unknown(packet),
+            _ => out = DecodedPacket::Unknown(packet.to_vec()),
         },
         0x04 => match state {
             ServerState::Play => out = DecodedPacket::SpawnPlayer(SpawnPlayer::decode(&mut pd)),
             ServerState::Login => {
                 out = DecodedPacket::LoginPluginRequest(LoginPluginRequest::decode(&mut pd))
             }
-            _ => out = DecodedPacket::Unknown(packet),
+            _ => out = DecodedPacket::Unknown(packet.to_vec()),
         },
         0x05 => out = DecodedPacket::SculkVibrationSignal(SculkVibrationSignal::decode(&mut pd)),
         0x06 => out = DecodedPacket::EntityAnimation(EntityAnimation::decode(&mut pd)),
@@ -1867,31 +1811,75 @@ pub fn decode_packet(packet: Vec<u8>, state: &ServerState) -> DecodedPacket {
         0x5c => out = DecodedPacket::SoundEffect(SoundEffect::decode(&mut pd)),
         0x61 => out = DecodedPacket::EntityTeleport(EntityTeleport::decode(&mut pd)),
         0x63 => out = DecodedPacket::EntityProperties(EntityProperties::decode(&mut pd)),
-        _ => out = DecodedPacket::Unknown(packet),
+        _ => out = DecodedPacket::Unknown(packet.to_vec()),
     }
 
-    match &out {
-        DecodedPacket::Unknown(pack) => {
-            debug!("Unknown packet: {:02x}", pack[0]);
-        }
-        _ => {}
+    if let Some(e) = pd.error {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Malformed packet {:02x}: {}", packet[0], e),
+        ));
     }
 
-    out
+    if let DecodedPacket::Unknown(pack) = &out {
+        debug!("Unknown packet: {:02x}", pack[0]);
+    }
+
+    Ok(out)
 }
 
 /// Packet Decoder walks a provided vector of bytes and extracts variables from them
+///
+/// Fields that can't be read, like a length running past the end of the packet, are recorded
+/// as an error instead of trusted, and read as empty.
 pub struct PacketDecoder<'a> {
-    buf: &'a Vec<u8>,
+    buf: &'a [u8],
     ind: usize,
+    // The first field that couldn't be read
+    error: Option<String>,
 }
 
 impl PacketDecoder<'_> {
     /// Create a packet decoder for a provided Vector
-    pub fn new<'a>(buf: &'a Vec<u8>, start_index: usize) -> PacketDecoder<'a> {
+    pub fn new<'a>(buf: &'a [u8], start_index: usize) -> PacketDecoder<'a> {
         PacketDecoder {
             buf,
             ind: start_index, // Start at 1 to skip the packet type signature
+            error: None,
+        }
+    }
+
+    /// Why the packet couldn't be decoded, if any field was malformed
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Reads the length prefix of a string or array with items at least `item_size` bytes long
+    ///
+    /// Lengths that are negative or need more bytes than are left in the packet are recorded
+    /// as an error and read as 0.
+    pub fn next_len(&mut self, item_size: usize) -> usize {
+        let (VarInt(len), vi_len) = VarInt::from_vec(self.buf, self.ind);
+        if vi_len == 0 {
+            self.fail(String::from("Missing or invalid length"));
+            return 0;
+        }
+        self.ind += vi_len;
+
+        let remaining = self.remaining();
+        match usize::try_from(len) {
+            Ok(len) if len.saturating_mul(item_size) <= remaining => len,
+            _ => {
+                self.fail(format!(
+                    "Length {} doesn't fit in the {} bytes left",
+                    len, remaining
+                ));
+                0
+            }
         }
     }
 
@@ -1903,88 +1891,120 @@ impl PacketDecoder<'_> {
         self.buf.len()
     }
 
+    /// How many bytes of the packet haven't been read yet
+    pub fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.ind)
+    }
+
+    // Reads the next N bytes of a fixed size field, or zeroes if the packet ends first
+    fn next_bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        if self.remaining() >= N {
+            bytes.copy_from_slice(&self.buf[self.ind..self.ind + N]);
+            self.ind += N;
+        } else {
+            self.fail(format!(
+                "{} byte field doesn't fit in the {} bytes left",
+                N,
+                self.remaining()
+            ));
+        }
+        bytes
+    }
+
     pub fn next_bool(&mut self) -> Boolean {
-        self.ind += 1;
-        match self.buf.get(self.ind - 1) {
-            Some(0x00) => Boolean(false),
-            Some(0x01) => Boolean(true),
-            _ => {
-                panic!("Invalid Boolean")
+        match self.next_bytes() {
+            [0x00] => Boolean(false),
+            [0x01] => Boolean(true),
+            [byte] => {
+                self.fail(format!("Invalid Boolean: {:02x}", byte));
+                Boolean(false)
             }
         }
     }
 
     pub fn next_byte(&mut self) -> Byte {
-        self.ind += 1;
-        Byte(self.buf[self.ind - 1] as i8)
+        Byte::from_bytes(&self.next_bytes())
     }
 
     pub fn next_ubyte(&mut self) -> UByte {
-        self.ind += 1;
-        UByte(self.buf[self.ind - 1])
+        UByte::from_bytes(&self.next_bytes())
     }
 
     pub fn next_short(&mut self) -> Short {
-        let bytes = extract_16(self.buf, self.ind);
-        self.ind += 2;
-        Short::from_bytes(&bytes)
+        Short::from_bytes(&self.next_bytes())
     }
 
     pub fn next_ushort(&mut self) -> UShort {
-        let bytes = extract_16(self.buf, self.ind);
-        self.ind += 2;
-        UShort::from_bytes(&bytes)
+        UShort::from_bytes(&self.next_bytes())
     }
 
     pub fn next_int(&mut self) -> Int {
-        let bytes = extract_32(self.buf, self.ind);
-        self.ind += 4;
-        Int::from_bytes(&bytes)
+        Int::from_bytes(&self.next_bytes())
     }
 
     pub fn next_long(&mut self) -> Long {
-        let bytes = extract_64(self.buf, self.ind);
-        self.ind += 8;
-        Long::from_bytes(&bytes)
+        Long::from_bytes(&self.next_bytes())
     }
 
     pub fn next_float(&mut self) -> Float {
-        let bytes = extract_32(self.buf, self.ind);
-        self.ind += 4;
-        Float::from_bytes(&bytes)
+        Float::from_bytes(&self.next_bytes())
     }
 
     pub fn next_double(&mut self) -> Double {
-        let bytes = extract_64(self.buf, self.ind);
-        self.ind += 8;
-        Double::from_bytes(&bytes)
+        Double::from_bytes(&self.next_bytes())
     }
 
     pub fn next_string(&mut self) -> MCString {
-        let (vi, vi_len) = VarInt::from_vec(self.buf, self.ind);
-        let str = MCString::from_bytes(&self.buf[self.ind..]);
-        self.ind += vi_len + vi.0 as usize;
-        str.expect("Invalid MCString")
+        let len = self.next_len(1);
+        let bytes = &self.buf[self.ind..self.ind + len];
+        self.ind += len;
+
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(str) => MCString(str),
+            Err(e) => {
+                self.fail(format!("Invalid MCString: {}", e));
+                MCString(String::new())
+            }
+        }
     }
 
     pub fn next_varint(&mut self) -> VarInt {
         let (vi, vi_len) = VarInt::from_vec(self.buf, self.ind);
+        if vi_len == 0 {
+            self.fail(String::from("Missing or invalid VarInt"));
+        }
         self.ind += vi_len;
         vi
     }
 
     pub fn next_varlong(&mut self) -> VarLong {
         let (vi, vi_len) = VarLong::from_vec(self.buf, self.ind);
+        if vi_len == 0 {
+            self.fail(String::from("Missing or invalid VarLong"));
+        }
         self.ind += vi_len;
         vi
     }
 
-    pub fn next_entity_metadata(&mut self) -> EntityMetadata {
-        todo!()
-    }
-
     pub fn next_slot(&mut self) -> Slot {
-        todo!(" Implement Packet Decoder Slot")
+        let present = self.next_bool();
+        if !present.0 {
+            return Slot(present, None, None, None);
+        }
+
+        let item_id = self.next_varint();
+        let count = self.next_byte();
+        // A lone end tag means the item has no NBT
+        let nbt = match self.buf.get(self.ind) {
+            Some(0x00) => {
+                self.ind += 1;
+                None
+            }
+            _ => Some(self.next_nbt()),
+        };
+
+        Slot(present, Some(item_id), Some(count), nbt)
     }
 
     pub fn next_nbt(&mut self) -> NBTTag {
@@ -1992,18 +2012,19 @@ impl PacketDecoder<'_> {
         cursor.set_position(self.ind as u64);
 
         match io::read_nbt(&mut cursor, io::Flavor::Uncompressed) {
-            Ok((nbt, name)) => {
+            Ok((nbt, _name)) => {
                 self.ind = cursor.position() as usize;
-                return NBTTag(nbt);
+                NBTTag(nbt)
             }
             Err(e) => {
-                panic!("Failed to decode NBT data: {}", e);
+                self.fail(format!("Failed to decode NBT data: {}", e));
+                NBTTag(NbtCompound::new())
             }
         }
     }
 
     pub fn next_position(&mut self) -> Position {
-        let big = u64::from_be_bytes(extract_64(self.buf, self.ind));
+        let big = u64::from_be_bytes(self.next_bytes());
 
         let mut x = (big >> 38) as i32;
         let mut y = (big & 0xfff) as i32;
@@ -2019,7 +2040,6 @@ impl PacketDecoder<'_> {
             z -= 2i32.pow(26)
         }
 
-        self.ind += 8;
         Position(x, y, z)
     }
 
@@ -2028,71 +2048,14 @@ impl PacketDecoder<'_> {
     }
 
     pub fn next_uuid(&mut self) -> UUID {
-        let mut b1 = [0u8; 8];
-        let mut b2 = [0u8; 8];
-        for i in 0..16 {
-            if i < 8 {
-                b1[i] = self.buf[i]
-            }
-            if i >= 8 {
-                b2[i - 8] = self.buf[i]
-            }
-        }
-        self.ind += 16;
-        UUID([u64::from_be_bytes(b1), u64::from_be_bytes(b2)])
+        UUID([
+            u64::from_be_bytes(self.next_bytes()),
+            u64::from_be_bytes(self.next_bytes()),
+        ])
     }
 
     pub fn print_remaining_bytes(&self) {
         println!("Printing remaining bytes:");
-        println!("{:02x?}", &self.buf[self.ind..]);
+        println!("{:02x?}", self.buf.get(self.ind..).unwrap_or_default());
     }
 }
-
-// These functions just extract arrays of specific length from a given buffer at a starting index
-// Really, nothing to see here
-
-fn extract_8(buf: &Vec<u8>, start: usize) -> [u8; 1] {
-    [buf[start]]
-}
-
-fn extract_16(buf: &Vec<u8>, start: usize) -> [u8; 2] {
-    [buf[start], buf[start + 1]]
-}
-
-fn extract_32(buf: &Vec<u8>, start: usize) -> [u8; 4] {
-    [buf[start], buf[start + 1], buf[start + 2], buf[start + 3]]
-}
-
-fn extract_64(buf: &Vec<u8>, start: usize) -> [u8; 8] {
-    [
-        buf[start],
-        buf[start + 1],
-        buf[start + 2],
-        buf[start + 3],
-        buf[start + 4],
-        buf[start + 5],
-        buf[start + 6],
-        buf[start + 7],
-    ]
-}
-
-fn extract_128(buf: &Vec<u8>, start: usize) -> [u8; 16] {
-    [
-        buf[start],
-        buf[start + 1],
-        buf[start + 2],
-        buf[start + 3],
-        buf[start + 4],
-        buf[start + 5],
-        buf[start + 6],
-        buf[start + 7],
-        buf[start + 8],
-        buf[start + 9],
-        buf[start + 10],
-        buf[start + 11],
-        buf[start + 12],
-        buf[start + 13],
-        buf[start + 14],
-        buf[start + 15],
-    ]
-}
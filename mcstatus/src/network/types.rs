@@ -4,8 +4,7 @@ use std::io;
 use std::string::FromUtf8Error;
 
 use quartz_nbt::NbtCompound;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 // Structs for each of the types used in the packets sent by an MC server
 
@@ -38,12 +37,18 @@ pub struct VarLong(pub i64);
 #[derive(Debug, Clone)]
 pub struct EntityMetadata(); // TODO
 #[derive(Debug, Clone)]
-pub struct Slot(Boolean, Option<VarInt>, Option<Byte>, Option<NBTTag>); // TODO
+pub struct Slot(
+    pub Boolean,
+    pub Option<VarInt>,
+    pub Option<Byte>,
+    pub Option<NBTTag>,
+);
 #[derive(Debug, Clone)]
 pub struct NBTTag(pub NbtCompound); // TODO
 #[derive(Debug, Clone)]
 pub struct Position(pub i32, pub i32, pub i32); // TODO
 pub type Angle = UByte;
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct UUID(pub [u64; 2]);
 
@@ -60,7 +65,7 @@ impl Boolean {
     }
 
     pub fn from_bytes(val: &[u8]) -> Option<Boolean> {
-        match val.get(0) {
+        match val.first() {
             Some(0x00) => Some(Boolean(false)),
             Some(0x01) => Some(Boolean(true)),
             _ => None,
@@ -189,13 +194,16 @@ impl VarInt {
         Some(VarInt(val as i32))
     }
 
-    pub fn from_vec(buf: &Vec<u8>, start: usize) -> (VarInt, usize) {
+    pub fn from_vec(buf: &[u8], start: usize) -> (VarInt, usize) {
         const PART: u32 = 0x7F;
         let mut size = 0;
         let mut val = 0u32;
 
         let mut index = 0usize;
-        let mut byte = buf[index + start];
+        let mut byte = match buf.get(start) {
+            Some(byte) => *byte,
+            None => return (VarInt(0), 0),
+        };
 
         loop {
             val |= (byte as u32 & PART) << (size * 7);
@@ -230,11 +238,11 @@ impl VarInt {
         }
     }
 
-    /// Reads a varint from a TcpStream, consuming the bytes
+    /// Reads a varint from a stream, consuming the bytes
     ///
     /// # Arguments
     ///
-    /// * `TcpStream` - The TcpStream from which to read
+    /// * `stream` - The stream from which to read, such as a TcpStream or one half of it
     ///
     /// # Returns
     ///
@@ -242,7 +250,9 @@ impl VarInt {
     /// * `Err(e)` if there is an error reading it
     /// * `Ok(None)` I don't think it actually returns this, I should probably clean this up later
     ///
-    pub async fn from_stream(stream: &mut TcpStream) -> Result<Option<VarInt>, io::Error> {
+    pub async fn from_stream<R: AsyncRead + Unpin>(
+        stream: &mut R,
+    ) -> Result<Option<VarInt>, io::Error> {
         const PART: u32 = 0x7F;
         let mut size = 0;
         let mut val = 0u32;
@@ -324,13 +334,16 @@ impl VarLong {
         VarLong(val as i64)
     }
 
-    pub fn from_vec(buf: &Vec<u8>, start: usize) -> (VarLong, usize) {
+    pub fn from_vec(buf: &[u8], start: usize) -> (VarLong, usize) {
         const PART: u64 = 0x7F;
         let mut size = 0;
         let mut val = 0u64;
 
         let mut index = 0usize;
-        let mut byte = buf[index + start];
+        let mut byte = match buf.get(start) {
+            Some(byte) => *byte,
+            None => return (VarLong(0), 0),
+        };
 
         loop {
             val |= (byte as u64 & PART) << (size * 7);
@@ -0,0 +1,379 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    future::Future,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{self, TcpStream},
+};
+use tokio_util::codec::Framed;
+
+use crate::address::{AddressError, ServerAddress};
+use crate::network::{
+    codec::PacketCodec, packets::DecodedPacket, send_status_request, types::VarInt, ServerState,
+    PROTOCOL_UNKNOWN,
+};
+
+/// Port Minecraft servers listen on unless told otherwise
+pub const DEFAULT_PORT: u16 = 25565;
+
+/// The status of a server, as reported in its status response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    #[serde(default)]
+    pub version: Version,
+    pub players: Option<Players>,
+    // Either a plain string or a chat component
+    #[serde(default)]
+    pub description: Value,
+    pub favicon: Option<String>,
+    // Mod list sent by later forge versions
+    #[serde(rename = "forgeData", skip_serializing_if = "Option::is_none")]
+    pub forge_data: Option<ForgeData>,
+    // Mod list sent by earlier forge versions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modinfo: Option<ModInfo>,
+    /// Round trip time of the status ping, if the server answered it
+    #[serde(skip)]
+    pub latency: Option<Duration>,
+}
+
+// Fields that are only informational default to empty, so a server leaving one out still answers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Version {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Players {
+    pub max: i64,
+    pub online: i64,
+    #[serde(default)]
+    pub sample: Vec<Player>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub name: String,
+    #[serde(default)]
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForgeData {
+    #[serde(default)]
+    pub mods: Vec<ForgeMod>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForgeMod {
+    #[serde(rename = "modId")]
+    pub mod_id: String,
+    #[serde(default)]
+    pub modmarker: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModInfo {
+    #[serde(rename = "modList", default)]
+    pub mod_list: Vec<ModInfoMod>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModInfoMod {
+    pub modid: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+/// A mod running on a server, whichever forge version reported it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mod {
+    pub id: String,
+    pub version: String,
+}
+
+impl ServerStatus {
+    /// Flattens the description into plain text, dropping any formatting
+    pub fn motd(&self) -> String {
+        let mut motd = String::new();
+        flatten_chat(&self.description, &mut motd);
+        motd
+    }
+
+    /// Every mod the server reports, from either forge mod list
+    pub fn mods(&self) -> Vec<Mod> {
+        let mut mods = Vec::new();
+
+        if let Some(forge_data) = &self.forge_data {
+            mods.extend(forge_data.mods.iter().map(|m| Mod {
+                id: m.mod_id.clone(),
+                version: m.modmarker.clone(),
+            }));
+        }
+
+        if let Some(modinfo) = &self.modinfo {
+            mods.extend(modinfo.mod_list.iter().map(|m| Mod {
+                id: m.modid.clone(),
+                version: m.version.clone(),
+            }));
+        }
+
+        mods
+    }
+}
+
+// Walks a chat component collecting its text
+fn flatten_chat(chat: &Value, out: &mut String) {
+    match chat {
+        Value::String(text) => out.push_str(text),
+        Value::Array(parts) => parts.iter().for_each(|part| flatten_chat(part, out)),
+        Value::Object(component) => {
+            if let Some(Value::String(text)) = component.get("text") {
+                out.push_str(text);
+            }
+            if let Some(extra) = component.get("extra") {
+                flatten_chat(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Why a server's status could not be retrieved
+#[derive(Debug)]
+pub enum PingError {
+    /// The address isn't one a server could be at
+    Address(AddressError),
+    /// Could not connect to the server
+    Connect(io::Error),
+    /// The server broke the protocol or hung up before responding
+    Protocol(Box<dyn Error + Send + Sync>),
+    /// The status response was not the JSON we expected
+    Json(serde_json::Error),
+    /// The server took too long to respond
+    Timeout,
+}
+
+impl PingError {
+    /// Short name for the kind of failure, for logs and metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PingError::Address(_) => "address",
+            PingError::Connect(_) => "connect",
+            PingError::Protocol(_) => "protocol",
+            PingError::Json(_) => "json",
+            PingError::Timeout => "timeout",
+        }
+    }
+}
+
+impl Display for PingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingError::Address(e) => write!(f, "Invalid server address: {}", e),
+            PingError::Connect(e) => write!(f, "Couldn't connect to server: {}", e),
+            PingError::Protocol(e) => write!(f, "Failed to retrieve status from server: {}", e),
+            PingError::Json(e) => write!(f, "Error interpretting JSON response: {}", e),
+            PingError::Timeout => write!(f, "Server took too long to respond"),
+        }
+    }
+}
+
+impl Error for PingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PingError::Address(e) => Some(e),
+            PingError::Connect(e) => Some(e),
+            PingError::Protocol(e) => Some(e.as_ref()),
+            PingError::Json(e) => Some(e),
+            PingError::Timeout => None,
+        }
+    }
+}
+
+impl From<AddressError> for PingError {
+    fn from(e: AddressError) -> PingError {
+        PingError::Address(e)
+    }
+}
+
+/// Looks up the socket address a connection to `host:port`, or `host` on the default port, would use
+pub async fn resolve(addr: &str) -> Result<SocketAddr, PingError> {
+    Ok(resolve_all(addr).await?[0])
+}
+
+/// Looks up every socket address `host:port`, or `host` on the default port, resolves to
+///
+/// There is always at least one, in the order the resolver gave them.
+pub async fn resolve_all(addr: &str) -> Result<Vec<SocketAddr>, PingError> {
+    let addr: ServerAddress = addr.parse()?;
+    let found: Vec<SocketAddr> = net::lookup_host((addr.host.as_str(), addr.port))
+        .await
+        .map_err(PingError::Connect)?
+        .collect();
+
+    if found.is_empty() {
+        return Err(PingError::Connect(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No addresses found for {}", addr.host),
+        )));
+    }
+
+    Ok(found)
+}
+
+/// Opens a connection to the server at `host:port`, or `host` on the default port
+pub async fn connect(addr: &str) -> Result<TcpStream, PingError> {
+    let addr: ServerAddress = addr.parse()?;
+    TcpStream::connect((addr.host.as_str(), addr.port))
+        .await
+        .map_err(PingError::Connect)
+}
+
+/// Settings for querying a server's status
+#[derive(Debug, Clone)]
+pub struct PingOptions {
+    /// Protocol version sent in the handshake, -1 if it doesn't matter
+    pub protocol_version: i32,
+    /// How long to wait for the server before giving up, None to wait forever
+    pub timeout: Option<Duration>,
+}
+
+impl Default for PingOptions {
+    fn default() -> PingOptions {
+        PingOptions {
+            protocol_version: PROTOCOL_UNKNOWN.0,
+            timeout: None,
+        }
+    }
+}
+
+/// Connects to a server and gets its status
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run() -> Result<(), mcstatus::PingError> {
+/// let status = mcstatus::ping("mc.example.com").await?;
+/// println!("{} players online", status.players.map(|p| p.online).unwrap_or(0));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn ping(addr: &str) -> Result<ServerStatus, PingError> {
+    ping_with(addr, &PingOptions::default()).await
+}
+
+/// Connects to a server and gets its status, with the timeout covering connecting as well
+pub async fn ping_with(addr: &str, options: &PingOptions) -> Result<ServerStatus, PingError> {
+    let addr: ServerAddress = addr.parse()?;
+    with_timeout(options.timeout, async {
+        let mut stream = TcpStream::connect((addr.host.as_str(), addr.port))
+            .await
+            .map_err(PingError::Connect)?;
+        ping_stream(&mut stream, &addr.host, options).await
+    })
+    .await
+}
+
+/// Gets the status of a server at an address that has already been resolved
+///
+/// `origin` is the host name the address was resolved from, sent to the server in the handshake.
+/// Nothing is looked up again, so the connection goes to exactly the address given.
+pub async fn ping_resolved(
+    addr: SocketAddr,
+    origin: &str,
+    options: &PingOptions,
+) -> Result<ServerStatus, PingError> {
+    with_timeout(options.timeout, async {
+        let mut stream = TcpStream::connect(addr).await.map_err(PingError::Connect)?;
+        ping_stream(&mut stream, origin, options).await
+    })
+    .await
+}
+
+/// Gets the status of a server over an already open connection
+///
+/// `origin` is the host name used to reach the server, sent to it in the handshake.
+/// Waits for the pong after the status response to measure latency, but a server hanging up
+/// instead of answering the ping is not treated as an error.
+pub async fn ping_stream<S>(
+    stream: &mut S,
+    origin: &str,
+    options: &PingOptions,
+) -> Result<ServerStatus, PingError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    with_timeout(
+        options.timeout,
+        exchange_status(stream, origin, VarInt(options.protocol_version)),
+    )
+    .await
+}
+
+async fn with_timeout<F>(timeout: Option<Duration>, query: F) -> Result<ServerStatus, PingError>
+where
+    F: Future<Output = Result<ServerStatus, PingError>>,
+{
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, query)
+            .await
+            .unwrap_or(Err(PingError::Timeout)),
+        None => query.await,
+    }
+}
+
+async fn exchange_status<S>(
+    stream: &mut S,
+    origin: &str,
+    protocol_version: VarInt,
+) -> Result<ServerStatus, PingError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(stream, PacketCodec::new(ServerState::Status));
+
+    let sent = Instant::now();
+    send_status_request(&mut framed, origin, protocol_version)
+        .await
+        .map_err(PingError::Protocol)?;
+
+    let response = loop {
+        match framed.next().await {
+            Some(Ok(DecodedPacket::StatusResponse(response))) => break response,
+            Some(Ok(DecodedPacket::Empty)) => info!("No response"),
+            Some(Ok(packet)) => {
+                return Err(PingError::Protocol(
+                    format!("Unexpected packet: {:?}", packet).into(),
+                ))
+            }
+            Some(Err(e)) => return Err(PingError::Protocol(Box::new(e))),
+            None => {
+                return Err(PingError::Protocol(
+                    "Server did not send a status response".into(),
+                ))
+            }
+        }
+    };
+
+    let mut status: ServerStatus =
+        serde_json::from_str(&response.response.0).map_err(PingError::Json)?;
+
+    if let Some(Ok(DecodedPacket::StatusPong(_))) = framed.next().await {
+        status.latency = Some(sent.elapsed());
+    }
+
+    Ok(status)
+}
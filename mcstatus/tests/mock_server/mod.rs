@@ -0,0 +1,213 @@
+//! A scripted stand-in for a Minecraft server, used to test the status pipeline end to end
+//!
+//! Every connection accepted by the mock runs through the same script, and everything the
+//! client sends is recorded so tests can check what went over the wire.
+
+#![allow(dead_code)]
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mcstatus::network::{packets::*, types::*};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    task::JoinHandle,
+};
+
+/// A step the mock server takes after the client connects
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// Wait for the handshake and status request, then send a StatusResponse with this JSON
+    Status(String),
+    /// Wait for the status ping, then echo its payload back in a StatusPong
+    Pong,
+    /// Do nothing for a while
+    Delay(Duration),
+    /// Send these bytes as they are, for malformed frames
+    Raw(Vec<u8>),
+    /// Hang up on the client
+    Disconnect,
+}
+
+/// A serverbound packet as the mock server saw it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received {
+    Handshake {
+        protocol_version: i32,
+        origin: String,
+        port: u16,
+        next_state: i32,
+    },
+    StatusRequest,
+    StatusPing(i64),
+    Unknown(Vec<u8>),
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    connections: Arc<AtomicUsize>,
+    received: Arc<Mutex<Vec<Received>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts listening on a free local port, answering every connection with the script
+    pub async fn start(script: Vec<Reply>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = {
+            let connections = connections.clone();
+            let received = received.clone();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(run_script(stream, script.clone(), received.clone()));
+                }
+            })
+        };
+
+        MockServer {
+            addr,
+            connections,
+            received,
+            handle,
+        }
+    }
+
+    /// A server that answers a status query like a vanilla server would
+    pub async fn vanilla(json: &str) -> MockServer {
+        MockServer::start(vec![Reply::Status(json.to_string()), Reply::Pong]).await
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// How many connections have been accepted so far
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Every packet received so far, across all connections
+    pub async fn received(&self) -> Vec<Received> {
+        self.received.lock().await.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn run_script(
+    mut stream: TcpStream,
+    script: Vec<Reply>,
+    received: Arc<Mutex<Vec<Received>>>,
+) {
+    for reply in script {
+        match reply {
+            Reply::Status(json) => {
+                if !wait_for(&mut stream, &received, |p| *p == Received::StatusRequest).await {
+                    return;
+                }
+
+                let mut response = Packet::new_with_id(StatusResponse::ID);
+                response.add(&MCString(json).to_bytes());
+                if send(&mut stream, &response).await.is_err() {
+                    return;
+                }
+            }
+            Reply::Pong => {
+                if !wait_for(&mut stream, &received, |p| {
+                    matches!(p, Received::StatusPing(_))
+                })
+                .await
+                {
+                    return;
+                }
+
+                let payload = match received.lock().await.last() {
+                    Some(Received::StatusPing(payload)) => *payload,
+                    _ => unreachable!(),
+                };
+
+                let mut pong = Packet::new_with_id(StatusPong::ID);
+                pong.add(&Long(payload).to_bytes());
+                if send(&mut stream, &pong).await.is_err() {
+                    return;
+                }
+            }
+            Reply::Delay(duration) => tokio::time::sleep(duration).await,
+            Reply::Raw(bytes) => {
+                if stream.write_all(&bytes).await.is_err() {
+                    return;
+                }
+            }
+            Reply::Disconnect => return,
+        }
+    }
+
+    // Keep the connection open until the client is done with it
+    let mut rest = Vec::new();
+    let _ = stream.read_to_end(&mut rest).await;
+}
+
+async fn send(stream: &mut TcpStream, packet: &Packet) -> std::io::Result<()> {
+    stream.write_all(&packet.get_bytes_with_length()).await
+}
+
+/// Reads and records packets until one matches, returning false if the client hung up first
+async fn wait_for(
+    stream: &mut TcpStream,
+    received: &Mutex<Vec<Received>>,
+    until: impl Fn(&Received) -> bool,
+) -> bool {
+    loop {
+        let packet = match read_packet(stream).await {
+            Some(packet) => packet,
+            None => return false,
+        };
+
+        let done = until(&packet);
+        received.lock().await.push(packet);
+
+        if done {
+            return true;
+        }
+    }
+}
+
+/// Reads a serverbound packet, decoding the ones a status query uses
+async fn read_packet(stream: &mut TcpStream) -> Option<Received> {
+    let VarInt(len) = VarInt::from_stream(stream).await.ok()??;
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame).await.ok()?;
+
+    let mut pd = PacketDecoder::new(&frame, 1);
+
+    // Status mode reuses the handshake ID, so tell them apart by size
+    Some(match (frame.first(), frame.len()) {
+        (Some(0x00), 1) => Received::StatusRequest,
+        (Some(0x00), _) => Received::Handshake {
+            protocol_version: pd.next_varint().0,
+            origin: pd.next_string().0,
+            port: pd.next_ushort().0,
+            next_state: pd.next_varint().0,
+        },
+        (Some(0x01), 9) => Received::StatusPing(pd.next_long().0),
+        _ => Received::Unknown(frame),
+    })
+}
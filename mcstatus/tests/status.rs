@@ -0,0 +1,363 @@
+mod mock_server;
+
+use std::{error::Error, net::SocketAddr, time::Duration};
+
+use mcstatus::network::{self, packets::*, types::*};
+use mcstatus::PingError;
+use mock_server::{MockServer, Received, Reply};
+use serde_json::Value;
+use tokio::net::TcpStream;
+
+const VANILLA: &str = r#"{"version":{"name":"1.18.1","protocol":757},"players":{"max":20,"online":2,"sample":[{"name":"Notch","id":"069a79f4-44e9-4726-a5be-fca90e38aaf5"},{"name":"jeb_","id":"853c80ef-3c37-49fd-aa49-938b674adae6"}]},"description":{"text":"A Minecraft Server"}}"#;
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Drives the status pipeline the way the bot does: connect, query, then interpret the JSON
+async fn query(addr: SocketAddr) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let response =
+        tokio::time::timeout(TIMEOUT, network::status(&mut stream, "localhost")).await??;
+    Ok(serde_json::from_str(&response.response.0)?)
+}
+
+#[tokio::test]
+async fn reads_vanilla_status() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    let json = query(server.addr()).await.unwrap();
+
+    assert_eq!(json["version"]["name"], "1.18.1");
+    assert_eq!(json["players"]["online"], 2);
+    assert_eq!(json["players"]["sample"][1]["name"], "jeb_");
+    assert_eq!(json["description"]["text"], "A Minecraft Server");
+}
+
+#[tokio::test]
+async fn sends_status_handshake() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    query(server.addr()).await.unwrap();
+
+    let received = server.received().await;
+    match &received[0] {
+        Received::Handshake {
+            origin, next_state, ..
+        } => {
+            assert_eq!(origin, "localhost");
+            assert_eq!(*next_state, 1);
+        }
+        other => panic!("Expected handshake first, got {:?}", other),
+    }
+    assert_eq!(received[1], Received::StatusRequest);
+    assert_eq!(server.connections(), 1);
+}
+
+#[tokio::test]
+async fn answers_ping_with_pong() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    let stream = TcpStream::connect(server.addr()).await.unwrap();
+    let channel = network::NetworkChannel::spawn(stream, "localhost".to_string());
+    let mut packets = channel.subscribe();
+    channel.status().await.unwrap();
+
+    assert!(matches!(
+        *packets.recv().await.unwrap(),
+        DecodedPacket::StatusResponse(_)
+    ));
+    let pong = match &*packets.recv().await.unwrap() {
+        DecodedPacket::StatusPong(pong) => pong.payload.0,
+        other => panic!("Expected pong, got {:?}", other),
+    };
+
+    let sent = server.received().await.iter().find_map(|p| match p {
+        Received::StatusPing(payload) => Some(*payload),
+        _ => None,
+    });
+    assert_eq!(sent, Some(pong));
+}
+
+#[tokio::test]
+async fn status_arriving_after_a_delay() {
+    let server = MockServer::start(vec![
+        Reply::Delay(Duration::from_millis(100)),
+        Reply::Status(VANILLA.to_string()),
+    ])
+    .await;
+
+    assert!(query(server.addr()).await.is_ok());
+}
+
+#[tokio::test]
+async fn times_out_on_silent_server() {
+    let server = MockServer::start(vec![Reply::Delay(Duration::from_secs(10))]).await;
+
+    assert!(query(server.addr()).await.is_err());
+}
+
+#[tokio::test]
+async fn fails_when_server_disconnects() {
+    let server = MockServer::start(vec![Reply::Disconnect]).await;
+
+    assert!(query(server.addr()).await.is_err());
+}
+
+#[tokio::test]
+async fn fails_on_truncated_frame() {
+    let mut response = Packet::new_with_id(StatusResponse::ID);
+    response.add(&MCString(VANILLA.to_string()).to_bytes());
+    let mut bytes = response.get_bytes_with_length();
+    bytes.truncate(bytes.len() / 2);
+
+    let server = MockServer::start(vec![Reply::Raw(bytes), Reply::Disconnect]).await;
+
+    assert!(query(server.addr()).await.is_err());
+}
+
+#[tokio::test]
+async fn fails_on_oversized_length_prefix() {
+    let server = MockServer::start(vec![Reply::Raw(vec![0xFF; 6])]).await;
+
+    assert!(query(server.addr()).await.is_err());
+}
+
+#[tokio::test]
+async fn refuses_status_packet_too_long_for_status() {
+    // Claims a 1 MiB status response, then never sends it
+    let server = MockServer::start(vec![
+        Reply::Raw(VarInt(1 << 20).to_bytes()),
+        Reply::Delay(Duration::from_secs(5)),
+    ])
+    .await;
+
+    // Refused as soon as the length arrives instead of waiting for the rest
+    let error = query(server.addr()).await.unwrap_err();
+    assert!(error.to_string().contains("exceeds"), "{}", error);
+}
+
+#[tokio::test]
+async fn refuses_status_string_longer_than_packet() {
+    let mut response = Packet::new_with_id(StatusResponse::ID);
+    response.add(&VarInt(i32::MAX).to_bytes());
+    response.add(b"{}");
+
+    let server = MockServer::start(vec![Reply::Raw(response.get_bytes_with_length())]).await;
+
+    let error = query(server.addr()).await.unwrap_err();
+    assert!(error.to_string().contains("Malformed"), "{}", error);
+}
+
+#[tokio::test]
+async fn fails_on_unexpected_packet() {
+    // A Play packet where a status response should be
+    let mut packet = Packet::new_with_id(0x05);
+    packet.add_byte(0x00);
+
+    let server = MockServer::start(vec![Reply::Raw(packet.get_bytes_with_length())]).await;
+
+    assert!(query(server.addr()).await.is_err());
+}
+
+#[tokio::test]
+async fn reports_invalid_json() {
+    let server = MockServer::vanilla("{not json").await;
+
+    let mut stream = TcpStream::connect(server.addr()).await.unwrap();
+    let response = network::status(&mut stream, "localhost").await.unwrap();
+
+    assert!(serde_json::from_str::<Value>(&response.response.0).is_err());
+}
+
+#[tokio::test]
+async fn each_query_opens_a_connection() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    for _ in 0..3 {
+        query(server.addr()).await.unwrap();
+    }
+
+    assert_eq!(server.connections(), 3);
+    assert_eq!(
+        server
+            .received()
+            .await
+            .iter()
+            .filter(|p| **p == Received::StatusRequest)
+            .count(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn ping_parses_server_status() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    let status = mcstatus::ping(&server.addr().to_string()).await.unwrap();
+
+    assert_eq!(status.version.name, "1.18.1");
+    assert_eq!(status.version.protocol, 757);
+    let players = status.players.unwrap();
+    assert_eq!((players.online, players.max), (2, 20));
+    assert_eq!(players.sample[0].name, "Notch");
+    assert!(status.latency.is_some());
+}
+
+#[tokio::test]
+async fn ping_sends_the_host_it_was_given() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    let addr = format!("localhost:{}", server.addr().port());
+    mcstatus::ping(&addr).await.unwrap();
+
+    // The name that was asked for, not the address it resolved to
+    match &server.received().await[0] {
+        Received::Handshake { origin, .. } => assert_eq!(origin, "localhost"),
+        other => panic!("Expected handshake first, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn ping_flattens_chat_description_and_mods() {
+    let json = r#"{"version":{"name":"1.16.5","protocol":754},"description":{"text":"Modded ","extra":[{"text":"Survival","bold":true}]},"forgeData":{"mods":[{"modId":"minecraft","modmarker":"1.16.5"},{"modId":"jei","modmarker":"7.7.1"}]}}"#;
+    let server = MockServer::vanilla(json).await;
+
+    let status = mcstatus::ping(&server.addr().to_string()).await.unwrap();
+
+    assert_eq!(status.motd(), "Modded Survival");
+    assert!(status.players.is_none());
+    assert_eq!(status.mods().len(), 2);
+    assert_eq!(status.mods()[1].id, "jei");
+}
+
+#[tokio::test]
+async fn ping_tolerates_partial_forge_data_and_samples() {
+    // No protocol, a player without an ID and mods without versions
+    let json = r#"{"version":{"name":"1.12.2"},"players":{"max":10,"online":1,"sample":[{"name":"Steve"}]},"description":"Old Forge","modinfo":{"type":"FML","modList":[{"modid":"forge"}]},"forgeData":{"mods":[{"modId":"jei"}]}}"#;
+    let server = MockServer::vanilla(json).await;
+
+    let status = mcstatus::ping(&server.addr().to_string()).await.unwrap();
+
+    assert_eq!(status.version.name, "1.12.2");
+    assert_eq!(status.version.protocol, 0);
+    assert_eq!(status.players.as_ref().unwrap().sample[0].name, "Steve");
+    assert_eq!(status.motd(), "Old Forge");
+    let ids: Vec<String> = status.mods().into_iter().map(|m| m.id).collect();
+    assert_eq!(ids, ["jei", "forge"]);
+
+    // A status without a version at all still parses
+    let server = MockServer::vanilla(r#"{"description":"Bare"}"#).await;
+    let status = mcstatus::ping(&server.addr().to_string()).await.unwrap();
+    assert_eq!(status.version.name, "");
+}
+
+#[tokio::test]
+async fn ping_without_pong_has_no_latency() {
+    let server =
+        MockServer::start(vec![Reply::Status(VANILLA.to_string()), Reply::Disconnect]).await;
+
+    let status = mcstatus::ping(&server.addr().to_string()).await.unwrap();
+
+    assert!(status.latency.is_none());
+}
+
+#[tokio::test]
+async fn ping_reports_error_kinds() {
+    let server = MockServer::vanilla("{not json").await;
+    assert!(matches!(
+        mcstatus::ping(&server.addr().to_string()).await,
+        Err(PingError::Json(_))
+    ));
+
+    let server = MockServer::start(vec![Reply::Disconnect]).await;
+    assert!(matches!(
+        mcstatus::ping(&server.addr().to_string()).await,
+        Err(PingError::Protocol(_))
+    ));
+
+    // Nothing listens on the port of a server that has been dropped
+    let addr = server.addr().to_string();
+    drop(server);
+    assert!(matches!(
+        mcstatus::ping(&addr).await,
+        Err(PingError::Connect(_))
+    ));
+}
+
+#[tokio::test]
+async fn resolves_addresses_with_default_port() {
+    let server = MockServer::vanilla(VANILLA).await;
+    assert_eq!(
+        mcstatus::resolve(&server.addr().to_string()).await.unwrap(),
+        server.addr()
+    );
+
+    assert_eq!(
+        mcstatus::resolve("127.0.0.1").await.unwrap(),
+        SocketAddr::from(([127, 0, 0, 1], mcstatus::DEFAULT_PORT))
+    );
+}
+
+#[tokio::test]
+async fn ping_resolved_sends_the_origin_host() {
+    let server = MockServer::vanilla(VANILLA).await;
+
+    let status = mcstatus::ping_resolved(
+        server.addr(),
+        "mc.example.com",
+        &mcstatus::PingOptions::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(status.version.name, "1.18.1");
+
+    match &server.received().await[0] {
+        Received::Handshake { origin, .. } => assert_eq!(origin, "mc.example.com"),
+        other => panic!("Expected handshake first, got {:?}", other),
+    }
+}
+
+#[test]
+fn splits_addresses() {
+    let split = |addr: &str| {
+        let addr: mcstatus::ServerAddress = addr.parse().unwrap();
+        (addr.host, addr.port)
+    };
+
+    assert_eq!(
+        split("mc.example.com"),
+        ("mc.example.com".to_string(), 25565)
+    );
+    assert_eq!(
+        split("mc.example.com:1234"),
+        ("mc.example.com".to_string(), 1234)
+    );
+    assert_eq!(split("[::1]:1234"), ("::1".to_string(), 1234));
+    assert_eq!(split("[::1]"), ("::1".to_string(), 25565));
+    assert_eq!(split("2001:db8::1"), ("2001:db8::1".to_string(), 25565));
+}
+
+#[tokio::test]
+async fn refuses_bad_ports_instead_of_using_the_default() {
+    for addr in ["mc.example.com:abc", "localhost:0", "127.0.0.1:65536"] {
+        assert!(
+            matches!(
+                mcstatus::resolve(addr).await,
+                Err(PingError::Address(mcstatus::AddressError::InvalidPort(_)))
+            ),
+            "{}",
+            addr
+        );
+        assert!(matches!(
+            mcstatus::ping(addr).await,
+            Err(PingError::Address(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn resolves_bracketed_ipv6() {
+    assert_eq!(
+        mcstatus::resolve("[::1]:1234").await.unwrap(),
+        "[::1]:1234".parse::<SocketAddr>().unwrap()
+    );
+}
@@ -0,0 +1,325 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{Path, Query},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use log::{error, info};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serenity::model::id::GuildId;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache,
+    cache::CachedStatus,
+    settings::SETTINGS,
+    storage::{Scope, SERVERS},
+    MCServer,
+};
+
+const TOKEN_LEN: usize = 32;
+
+/// Creates a new API token for a guild, replacing any it had before
+pub fn new_token(guild: GuildId) -> String {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect();
+
+    SETTINGS.update(guild, |s| s.api_token_hash = Some(hash_token(&token)));
+    token
+}
+
+// Tokens are long and random, so a plain hash is enough to keep them out of the settings file
+fn hash_token(token: &str) -> String {
+    base64::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Serves the read-only API on the address until the bot stops
+pub async fn serve(addr: SocketAddr) {
+    let app = Router::new()
+        .route("/guilds/:guild/servers", get(list_servers))
+        .route("/guilds/:guild/servers/:name", get(get_server))
+        .route("/guilds/:guild/servers/:name/badge", get(get_badge));
+
+    info!("Serving API on http://{}", addr);
+    if let Err(e) = axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+    {
+        error!("API server stopped: {}", e);
+    }
+}
+
+// The token can come as a bearer token, or in the query string for things like badges in image tags
+#[derive(Deserialize)]
+struct Auth {
+    token: Option<String>,
+}
+
+type ApiResult = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+fn api_error(status: StatusCode, message: &str) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message })))
+}
+
+// Checks the request carries the guild's token, without leaking how much of it matched
+async fn authorize(
+    guild: GuildId,
+    headers: &HeaderMap,
+    auth: &Auth,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let given = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or(auth.token.as_deref());
+
+    match (SETTINGS.get(guild).api_token_hash, given) {
+        (Some(hash), Some(given))
+            if constant_time_eq(hash.as_bytes(), hash_token(given).as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => Err(api_error(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid API token",
+        )),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Serialize)]
+struct ServerInfo {
+    id: u64,
+    name: Option<String>,
+    tags: Vec<String>,
+    ip: String,
+    active: bool,
+    last_status: Option<CachedStatus>,
+}
+
+// Copies the guild's servers out, so the lock isn't held while reading the cache
+async fn guild_servers(guild: GuildId) -> Vec<(MCServer, bool)> {
+    SERVERS.read(Scope::Guild(guild), |gs| {
+        gs.servers
+            .iter()
+            .map(|s| (s.clone(), gs.active == Some(s.id)))
+            .collect()
+    })
+}
+
+async fn server_info((server, active): (MCServer, bool)) -> ServerInfo {
+    ServerInfo {
+        last_status: cache::get(&server.ip).await,
+        id: server.id,
+        tags: server.tags,
+        name: server.name,
+        ip: server.ip,
+        active,
+    }
+}
+
+async fn find_server(guild: GuildId, name: &str) -> Result<ServerInfo, (StatusCode, Json<Value>)> {
+    match guild_servers(guild)
+        .await
+        .into_iter()
+        .find(|(s, _)| s.has_name(name))
+    {
+        Some(server) => Ok(server_info(server).await),
+        None => Err(api_error(
+            StatusCode::NOT_FOUND,
+            "No saved server with that name",
+        )),
+    }
+}
+
+async fn list_servers(
+    Path(guild): Path<u64>,
+    Query(auth): Query<Auth>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let guild = GuildId(guild);
+    authorize(guild, &headers, &auth).await?;
+
+    let mut servers = Vec::new();
+    for server in guild_servers(guild).await {
+        servers.push(server_info(server).await);
+    }
+
+    Ok(Json(json!({ "servers": servers })))
+}
+
+async fn get_server(
+    Path((guild, name)): Path<(u64, String)>,
+    Query(auth): Query<Auth>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let guild = GuildId(guild);
+    authorize(guild, &headers, &auth).await?;
+
+    Ok(Json(json!(find_server(guild, &name).await?)))
+}
+
+/// Status in the shape of a shields.io endpoint badge
+async fn get_badge(
+    Path((guild, name)): Path<(u64, String)>,
+    Query(auth): Query<Auth>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let guild = GuildId(guild);
+    authorize(guild, &headers, &auth).await?;
+
+    let server = find_server(guild, &name).await?;
+
+    let (message, color) = match &server.last_status {
+        None => ("unknown".to_string(), "lightgrey"),
+        Some(CachedStatus { online: false, .. }) => ("offline".to_string(), "red"),
+        Some(CachedStatus {
+            status: Some(status),
+            ..
+        }) => match &status.players {
+            Some(players) => (
+                format!("{}/{} online", players.online, players.max),
+                "brightgreen",
+            ),
+            None => ("online".to_string(), "brightgreen"),
+        },
+        Some(_) => ("online".to_string(), "brightgreen"),
+    };
+
+    Ok(Json(json!({
+        "schemaVersion": 1,
+        // The saved name, rather than however the request spelled it
+        "label": server.name.unwrap_or(name),
+        "message": message,
+        "color": color,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn query(token: Option<&str>) -> Query<Auth> {
+        Query(Auth {
+            token: token.map(String::from),
+        })
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        );
+        headers
+    }
+
+    // A guild with one saved server and a token for the API
+    fn guild_with_server(guild: u64) -> String {
+        SERVERS.update(Scope::Guild(GuildId(guild)), |gs| {
+            gs.add(MCServer {
+                id: 0,
+                ip: String::from("localhost:25565"),
+                name: Some(String::from("Survival")),
+                tags: vec![String::from("smp")],
+            })
+        });
+        new_token(GuildId(guild))
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_guilds_token() {
+        let token = guild_with_server(331);
+        let other = guild_with_server(332);
+        let guild = GuildId(331);
+
+        assert!(authorize(guild, &bearer(&token), &query(None).0)
+            .await
+            .is_ok());
+        assert!(authorize(guild, &HeaderMap::new(), &query(Some(&token)).0)
+            .await
+            .is_ok());
+
+        for (headers, auth) in [
+            (HeaderMap::new(), query(None)),
+            (bearer("wrong"), query(None)),
+            (HeaderMap::new(), query(Some(&token[1..]))),
+            // Tokens only work for the guild they were made for
+            (bearer(&other), query(None)),
+        ] {
+            let (status, _) = authorize(guild, &headers, &auth.0).await.unwrap_err();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        // Only the hash is kept, and a new token replaces the old one
+        assert_ne!(SETTINGS.get(guild).api_token_hash.unwrap(), token);
+        new_token(guild);
+        assert!(authorize(guild, &bearer(&token), &query(None).0)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_and_finds_servers() {
+        let token = guild_with_server(333);
+
+        let Json(list) = list_servers(Path(333), query(Some(&token)), HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(list["servers"][0]["name"], "Survival");
+        assert_eq!(list["servers"][0]["tags"][0], "smp");
+        assert_eq!(list["servers"][0]["active"], true);
+
+        let Json(server) = get_server(
+            Path((333, String::from("survival"))),
+            query(None),
+            bearer(&token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(server["ip"], "localhost:25565");
+
+        let (status, _) = get_server(
+            Path((333, String::from("creative"))),
+            query(None),
+            bearer(&token),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = list_servers(Path(333), query(None), HeaderMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn badges_use_the_saved_name() {
+        let token = guild_with_server(334);
+
+        let Json(badge) = get_badge(
+            Path((334, String::from("SURVIVAL"))),
+            query(Some(&token)),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(badge["label"], "Survival");
+        // Never pinged yet
+        assert_eq!(badge["message"], "unknown");
+    }
+}
@@ -0,0 +1,123 @@
+use serenity::{
+    client::Context,
+    framework::standard::{Args, CommandOptions, CommandResult},
+    model::channel::Message,
+};
+
+// Longest name a saved server can have
+const MAX_NAME_LEN: usize = 64;
+// Longest tag a saved server can have
+const MAX_TAG_LEN: usize = 32;
+
+/// Replies with how a command should be used, from the usage in its metadata
+pub async fn usage_error(ctx: &Context, msg: &Message, options: &CommandOptions) -> CommandResult {
+    let usage = match options.usage {
+        Some(usage) => format!("{} {}", options.names[0], usage),
+        None => options.names[0].to_string(),
+    };
+
+    msg.reply(
+        ctx,
+        &format!("Improper command usage. Proper use:\n{}", usage),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The next argument, with any quotes around it removed
+pub fn next(args: &mut Args) -> Option<String> {
+    args.single_quoted::<String>().ok()
+}
+
+/// Checks a name can be used for a saved server
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err(String::from("Server names can't be empty"));
+    }
+
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!(
+            "Server names can be at most {} characters long",
+            MAX_NAME_LEN
+        ));
+    }
+
+    if name.contains(['\n', '`']) {
+        return Err(String::from("Server names can't contain new lines or `"));
+    }
+
+    Ok(())
+}
+
+/// Checks a tag can be given to a saved server
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN {
+        return Err(format!(
+            "Tags must be from 1 to {} characters long",
+            MAX_TAG_LEN
+        ));
+    }
+
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "`{}` isn't a valid tag, tags can only use letters, numbers, - and _",
+            tag
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::framework::standard::Delimiter;
+
+    use super::*;
+
+    // Splits arguments the way the framework does with its default delimiter
+    fn split(message: &str) -> Vec<String> {
+        let mut args = Args::new(message, &[Delimiter::Single(' ')]);
+        std::iter::from_fn(|| next(&mut args)).collect()
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(split("add mc.example.com"), ["add", "mc.example.com"]);
+        assert_eq!(
+            split(r#"rename Old "New Survival""#),
+            ["rename", "Old", "New Survival"]
+        );
+        assert_eq!(split(r#""Skyblock Hub" lobby"#), ["Skyblock Hub", "lobby"]);
+        assert_eq!(split(r#""""#), [""]);
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn validates_names() {
+        assert!(validate_name("Survival").is_ok());
+        assert!(validate_name("New Survival 2").is_ok());
+        assert!(validate_name(&"é".repeat(MAX_NAME_LEN)).is_ok());
+
+        assert!(validate_name("").is_err());
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(validate_name("two\nlines").is_err());
+        assert!(validate_name("`code`").is_err());
+    }
+
+    #[test]
+    fn validates_tags() {
+        assert!(validate_tag("network").is_ok());
+        assert!(validate_tag("eu-west_2").is_ok());
+        assert!(validate_tag(&"t".repeat(MAX_TAG_LEN)).is_ok());
+
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag(&"t".repeat(MAX_TAG_LEN + 1)).is_err());
+        assert!(validate_tag("two words").is_err());
+        assert!(validate_tag("group:hub").is_err());
+    }
+}
@@ -0,0 +1,515 @@
+use std::{collections::HashMap, fmt::Display};
+
+use mcstatus::ServerAddress;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::ChannelId;
+
+use crate::{args, storage::GuildServers, MCServer};
+
+/// Version written to exported files. Bump it when the format changes in a way older bots can't read
+pub const EXPORT_VERSION: u32 = 1;
+
+// Most servers a file can hold, or a list can have after importing one, so imports can't
+// make a list unusably long
+const MAX_IMPORT_SERVERS: usize = 500;
+// Names listed for each kind of change, so a preview fits in a message
+const PREVIEW_NAMES: usize = 10;
+
+/// A guild's servers and settings, as written to and read from an export file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub servers: Vec<ExportedServer>,
+    /// Name of the active server
+    #[serde(default)]
+    pub active: Option<String>,
+    /// Channels with their own active server
+    #[serde(default)]
+    pub channels: Vec<ExportedChannel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedServer {
+    pub name: String,
+    pub ip: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedChannel {
+    pub channel: u64,
+    /// Name of the server the channel uses
+    pub server: String,
+}
+
+// Read before the rest of the file, so a file from a newer bot gets a clear error
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+/// Why a file couldn't be imported
+#[derive(Debug)]
+pub enum ImportError {
+    Json(serde_json::Error),
+    Version(u32),
+    Invalid(String),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Json(err) => write!(f, "The file isn't a valid export: {}", err),
+            ImportError::Version(version) => write!(
+                f,
+                "The file is version {}, but only version {} can be imported",
+                version, EXPORT_VERSION
+            ),
+            ImportError::Invalid(why) => write!(f, "The file can't be imported: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// How imported servers are combined with the ones already saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Adds new servers and updates saved servers with the same name
+    Merge,
+    /// Removes every saved server first
+    Replace,
+}
+
+/// Names of the servers an import adds, changes or removes, and which servers end up active
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// The active server afterwards, if it isn't the one that was active before
+    pub active: Option<Option<String>>,
+    /// Channels whose own active server changes, with the one they use afterwards
+    pub channels: Vec<(ChannelId, Option<String>)>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.active.is_none()
+            && self.channels.is_empty()
+    }
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No servers change");
+        }
+
+        for (label, names) in [
+            ("Add", &self.added),
+            ("Update", &self.updated),
+            ("Remove", &self.removed),
+        ] {
+            if names.is_empty() {
+                continue;
+            }
+
+            let shown = &names[..names.len().min(PREVIEW_NAMES)];
+            write!(f, "{} {}: {}", label, names.len(), shown.join(", "))?;
+            if names.len() > shown.len() {
+                write!(f, " and {} more", names.len() - shown.len())?;
+            }
+            writeln!(f)?;
+        }
+
+        if let Some(active) = &self.active {
+            writeln!(f, "Active server: {}", active.as_deref().unwrap_or("none"))?;
+        }
+
+        if !self.channels.is_empty() {
+            let shown: Vec<String> = self
+                .channels
+                .iter()
+                .take(PREVIEW_NAMES)
+                .map(|(channel, name)| {
+                    format!("<#{}> {}", channel, name.as_deref().unwrap_or("none"))
+                })
+                .collect();
+            write!(f, "Channels: {}", shown.join(", "))?;
+            if self.channels.len() > shown.len() {
+                write!(f, " and {} more", self.channels.len() - shown.len())?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Export {
+    /// Everything saved in a list of servers
+    pub fn of(gs: &GuildServers) -> Export {
+        let name = |id: u64| gs.get(id).and_then(|s| s.name.clone());
+
+        let mut channels: Vec<ExportedChannel> = gs
+            .channels
+            .iter()
+            .filter_map(|(channel, id)| {
+                Some(ExportedChannel {
+                    channel: channel.0,
+                    server: name(*id)?,
+                })
+            })
+            .collect();
+        channels.sort_by_key(|c| c.channel);
+
+        Export {
+            version: EXPORT_VERSION,
+            servers: gs
+                .servers
+                .iter()
+                .filter_map(|s| {
+                    Some(ExportedServer {
+                        name: s.name.clone()?,
+                        ip: s.ip.clone(),
+                        tags: s.tags.clone(),
+                    })
+                })
+                .collect(),
+            active: gs.active.and_then(name),
+            channels,
+        }
+    }
+
+    /// Reads and checks an exported file, normalizing the addresses in it
+    pub fn parse(data: &[u8]) -> Result<Export, ImportError> {
+        let Versioned { version } = serde_json::from_slice(data).map_err(ImportError::Json)?;
+        if version != EXPORT_VERSION {
+            return Err(ImportError::Version(version));
+        }
+
+        let mut export: Export = serde_json::from_slice(data).map_err(ImportError::Json)?;
+
+        if export.servers.len() > MAX_IMPORT_SERVERS {
+            return Err(ImportError::Invalid(format!(
+                "it has {} servers, but at most {} can be imported",
+                export.servers.len(),
+                MAX_IMPORT_SERVERS
+            )));
+        }
+
+        for (i, serv) in export.servers.iter().enumerate() {
+            if export.servers[..i]
+                .iter()
+                .any(|s| s.name.to_lowercase() == serv.name.to_lowercase())
+            {
+                return Err(ImportError::Invalid(format!(
+                    "{} is in it more than once",
+                    serv.name
+                )));
+            }
+        }
+
+        for serv in &mut export.servers {
+            args::validate_name(&serv.name).map_err(ImportError::Invalid)?;
+
+            let address: ServerAddress = serv
+                .ip
+                .parse()
+                .map_err(|why| ImportError::Invalid(format!("{}: {}", serv.name, why)))?;
+            serv.ip = address.to_string();
+
+            for tag in &serv.tags {
+                args::validate_tag(tag).map_err(ImportError::Invalid)?;
+            }
+        }
+
+        let referenced = export
+            .active
+            .iter()
+            .chain(export.channels.iter().map(|c| &c.server));
+        for name in referenced {
+            if export.find(name).is_none() {
+                return Err(ImportError::Invalid(format!(
+                    "it uses {} as an active server, but doesn't have a server with that name",
+                    name
+                )));
+            }
+        }
+
+        Ok(export)
+    }
+
+    fn find(&self, name: &str) -> Option<&ExportedServer> {
+        self.servers
+            .iter()
+            .find(|s| s.name.to_lowercase() == name.to_lowercase())
+    }
+
+    /// Imports into a list of servers. Apply it to a copy to preview what would change
+    ///
+    /// Nothing changes if the list would end up with more servers than a file can hold.
+    pub fn apply(&self, gs: &mut GuildServers, mode: ImportMode) -> Result<Changes, ImportError> {
+        if mode == ImportMode::Merge {
+            let total = gs.servers.len()
+                + self
+                    .servers
+                    .iter()
+                    .filter(|s| gs.find(&s.name).is_none())
+                    .count();
+            if total > MAX_IMPORT_SERVERS {
+                return Err(ImportError::Invalid(format!(
+                    "merging it would make {} saved servers, but there can be at most {}",
+                    total, MAX_IMPORT_SERVERS
+                )));
+            }
+        }
+
+        let mut changes = Changes::default();
+
+        // Compared with afterwards by name, since replacing gives every server a new ID
+        let active_name = |gs: &GuildServers| gs.active_server().and_then(|s| s.name.clone());
+        let channel_names = |gs: &GuildServers| -> HashMap<ChannelId, String> {
+            gs.channels
+                .iter()
+                .filter_map(|(channel, id)| Some((*channel, gs.get(*id)?.name.clone()?)))
+                .collect()
+        };
+        let active_before = active_name(gs);
+        let channels_before = channel_names(gs);
+
+        // Servers that are replaced by one with the same name count as updated, not removed
+        let mut previous = Vec::new();
+        if mode == ImportMode::Replace {
+            changes.removed = gs
+                .servers
+                .iter()
+                .filter(|s| self.find(s.name.as_deref().unwrap_or_default()).is_none())
+                .map(ToString::to_string)
+                .collect();
+            previous = gs.servers.clone();
+            gs.clear();
+        }
+
+        for imported in &self.servers {
+            match gs.find(&imported.name) {
+                Some(i) => {
+                    let serv = &mut gs.servers[i];
+                    if serv.ip != imported.ip || serv.tags != imported.tags {
+                        serv.ip = imported.ip.clone();
+                        serv.tags = imported.tags.clone();
+                        changes.updated.push(serv.to_string());
+                    }
+                }
+                None => {
+                    let serv = MCServer {
+                        id: 0,
+                        ip: imported.ip.clone(),
+                        name: Some(imported.name.clone()),
+                        tags: imported.tags.clone(),
+                    };
+                    match previous.iter().find(|s| s.has_name(&imported.name)) {
+                        Some(old) if old.ip == serv.ip && old.tags == serv.tags => {}
+                        Some(_) => changes.updated.push(serv.to_string()),
+                        None => changes.added.push(serv.to_string()),
+                    }
+                    gs.add(serv);
+                }
+            }
+        }
+
+        // Servers are matched by name, which is unique, to find their IDs in this list
+        let id = |gs: &GuildServers, name: &str| gs.find(name).map(|i| gs.servers[i].id);
+
+        // Replacing takes the active servers from the file exactly, merging only the ones it sets
+        match (&self.active, mode) {
+            (Some(active), _) => gs.active = id(gs, active),
+            (None, ImportMode::Replace) => gs.active = None,
+            (None, ImportMode::Merge) => {}
+        }
+
+        if mode == ImportMode::Replace {
+            gs.channels.clear();
+        }
+        for binding in &self.channels {
+            if let Some(id) = id(gs, &binding.server) {
+                gs.channels.insert(ChannelId(binding.channel), id);
+            }
+        }
+
+        let active_after = active_name(gs);
+        if active_after.as_ref().map(|n| n.to_lowercase())
+            != active_before.as_ref().map(|n| n.to_lowercase())
+        {
+            changes.active = Some(active_after);
+        }
+
+        let channels_after = channel_names(gs);
+        let mut channels: Vec<ChannelId> = channels_before
+            .keys()
+            .chain(channels_after.keys())
+            .copied()
+            .collect();
+        channels.sort();
+        channels.dedup();
+        for channel in channels {
+            let (before, after) = (channels_before.get(&channel), channels_after.get(&channel));
+            if before.map(|n| n.to_lowercase()) != after.map(|n| n.to_lowercase()) {
+                changes.channels.push((channel, after.cloned()));
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved() -> GuildServers {
+        let mut gs = GuildServers::default();
+        for (name, ip) in [("survival", "localhost"), ("creative", "mc.example.com")] {
+            gs.add(MCServer::new(&ip.parse().unwrap(), Some(name.to_string())));
+        }
+        gs.servers[1].tags.push(String::from("lobby"));
+        gs.active = Some(gs.servers[1].id);
+        gs.channels.insert(ChannelId(7), gs.servers[0].id);
+        gs
+    }
+
+    fn parse_err(json: &str) -> ImportError {
+        Export::parse(json.as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let gs = saved();
+        let json = serde_json::to_vec(&Export::of(&gs)).unwrap();
+        let export = Export::parse(&json).unwrap();
+
+        let mut imported = GuildServers::default();
+        let changes = export.apply(&mut imported, ImportMode::Replace).unwrap();
+        assert_eq!(changes.added.len(), 2);
+        assert_eq!(changes.active, Some(Some(String::from("creative"))));
+        assert_eq!(
+            changes.channels,
+            [(ChannelId(7), Some(String::from("survival")))]
+        );
+
+        assert_eq!(Export::of(&imported), export);
+        assert_eq!(
+            imported.active_server().unwrap().name.as_deref(),
+            Some("creative")
+        );
+        assert_eq!(
+            imported.active_in(ChannelId(7)).unwrap().name.as_deref(),
+            Some("survival")
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_bad_servers() {
+        assert!(matches!(
+            parse_err(r#"{"version": 2, "servers": []}"#),
+            ImportError::Version(2)
+        ));
+        assert!(matches!(
+            parse_err(r#"{"servers": []}"#),
+            ImportError::Json(_)
+        ));
+        assert!(matches!(
+            parse_err(r#"{"version": 1, "servers": [{"name": "a", "ip": "mc example"}]}"#),
+            ImportError::Invalid(_)
+        ));
+        assert!(matches!(
+            parse_err(
+                r#"{"version": 1, "servers": [{"name": "a", "ip": "localhost"}, {"name": "A", "ip": "localhost"}]}"#
+            ),
+            ImportError::Invalid(_)
+        ));
+        assert!(matches!(
+            parse_err(
+                r#"{"version": 1, "servers": [{"name": "a", "ip": "localhost"}], "active": "b"}"#
+            ),
+            ImportError::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn merge_keeps_saved_servers_and_replace_removes_them() {
+        let export = Export::parse(
+            br#"{"version": 1, "servers": [
+                {"name": "Creative", "ip": "mc.example.com", "tags": ["lobby"]},
+                {"name": "modded", "ip": "[::1]:25566"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut merged = saved();
+        let changes = export.apply(&mut merged, ImportMode::Merge).unwrap();
+        assert_eq!(changes.added, ["modded ([::1]:25566)"]);
+        assert!(changes.updated.is_empty() && changes.removed.is_empty());
+        assert_eq!(merged.servers.len(), 3);
+        // Merging without an active server keeps the saved one
+        assert_eq!(
+            merged.active_server().unwrap().name.as_deref(),
+            Some("creative")
+        );
+
+        let mut replaced = saved();
+        let changes = export.apply(&mut replaced, ImportMode::Replace).unwrap();
+        assert_eq!(changes.added, ["modded ([::1]:25566)"]);
+        assert_eq!(changes.removed, ["survival (localhost:25565)"]);
+        assert_eq!(replaced.servers.len(), 2);
+        assert!(replaced.channels.is_empty());
+
+        // The file has no active server, so none is active afterwards
+        assert_eq!(replaced.active, None);
+        assert_eq!(changes.active, Some(None));
+        assert_eq!(changes.channels, [(ChannelId(7), None)]);
+        let preview = changes.to_string();
+        assert!(preview.contains("Active server: none"), "{}", preview);
+        assert!(preview.contains("<#7> none"), "{}", preview);
+    }
+
+    #[test]
+    fn replace_into_an_empty_list_leaves_no_active_server() {
+        let export =
+            Export::parse(br#"{"version": 1, "servers": [{"name": "a", "ip": "localhost"}]}"#)
+                .unwrap();
+
+        let mut gs = GuildServers::default();
+        let changes = export.apply(&mut gs, ImportMode::Replace).unwrap();
+
+        assert_eq!(gs.active, None);
+        assert_eq!(changes.active, None);
+    }
+
+    #[test]
+    fn merges_cant_grow_a_list_past_the_limit() {
+        let servers: Vec<String> = (0..MAX_IMPORT_SERVERS)
+            .map(|i| format!(r#"{{"name": "s{}", "ip": "localhost"}}"#, i))
+            .collect();
+        let full = format!(r#"{{"version": 1, "servers": [{}]}}"#, servers.join(","));
+        let full = Export::parse(full.as_bytes()).unwrap();
+        let one_more =
+            Export::parse(br#"{"version": 1, "servers": [{"name": "extra", "ip": "localhost"}]}"#)
+                .unwrap();
+
+        let mut gs = GuildServers::default();
+        full.apply(&mut gs, ImportMode::Merge).unwrap();
+        assert!(one_more.apply(&mut gs, ImportMode::Merge).is_err());
+        assert_eq!(gs.servers.len(), MAX_IMPORT_SERVERS);
+
+        // Merging the same servers again only updates them, and replacing starts over
+        assert!(full.apply(&mut gs, ImportMode::Merge).is_ok());
+        assert!(one_more.apply(&mut gs, ImportMode::Replace).is_ok());
+        assert_eq!(gs.servers.len(), 1);
+    }
+}
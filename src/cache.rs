@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use mcstatus::{PingError, ServerStatus};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serenity::prelude::{Mutex, RwLock};
+
+// How long a ping result is reused for, in seconds
+static TTL_SECS: AtomicU64 = AtomicU64::new(30);
+
+pub fn set_ttl(ttl: Duration) {
+    TTL_SECS.store(ttl.as_secs(), Ordering::Relaxed);
+}
+
+fn ttl() -> Duration {
+    Duration::from_secs(TTL_SECS.load(Ordering::Relaxed))
+}
+
+/// A ping result, shared by everyone who asked for the server while it was fresh
+pub struct Lookup {
+    pub result: Arc<Result<ServerStatus, PingError>>,
+    /// How long ago the server was pinged, or None if this request did the ping itself
+    pub age: Option<Duration>,
+}
+
+struct Pinged {
+    at: Instant,
+    result: Arc<Result<ServerStatus, PingError>>,
+}
+
+impl Pinged {
+    fn lookup(&self) -> Lookup {
+        Lookup {
+            result: self.result.clone(),
+            age: Some(self.at.elapsed()),
+        }
+    }
+}
+
+type Slot = Arc<Mutex<Option<Pinged>>>;
+
+// Latest ping of each resolved address. Each has its own lock, held for the whole ping,
+// so a request arriving while a ping is in flight waits for it instead of opening another connection
+#[derive(Default)]
+struct RecentPings {
+    slots: RwLock<HashMap<SocketAddr, Slot>>,
+}
+
+impl RecentPings {
+    async fn fresh(&self, addr: SocketAddr, ttl: Duration) -> Option<Lookup> {
+        let slot = self.slots.read().await.get(&addr)?.clone();
+        let slot = slot.try_lock().ok()?;
+
+        slot.as_ref()
+            .filter(|pinged| pinged.at.elapsed() < ttl)
+            .map(Pinged::lookup)
+    }
+
+    async fn status_or_ping<F, Fut>(
+        &self,
+        addr: SocketAddr,
+        ttl: Duration,
+        wait: Duration,
+        ping: F,
+    ) -> Lookup
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ServerStatus, PingError>>,
+    {
+        let asked = Instant::now();
+
+        let slot = {
+            let existing = self.slots.read().await.get(&addr).cloned();
+            match existing {
+                Some(slot) => slot,
+                None => {
+                    let mut slots = self.slots.write().await;
+                    prune(&mut slots, ttl);
+                    slots.entry(addr).or_default().clone()
+                }
+            }
+        };
+
+        // Don't wait on someone else's ping for longer than we'd wait on our own
+        let mut slot = match tokio::time::timeout(wait, slot.lock()).await {
+            Ok(slot) => slot,
+            Err(_) => {
+                return Lookup {
+                    result: Arc::new(Err(PingError::Timeout)),
+                    age: None,
+                }
+            }
+        };
+
+        // A ping that finished after we asked is the one we were waiting on, however short the TTL
+        if let Some(pinged) = slot
+            .as_ref()
+            .filter(|p| p.at >= asked || p.at.elapsed() < ttl)
+        {
+            return pinged.lookup();
+        }
+
+        let result = Arc::new(ping().await);
+        *slot = Some(Pinged {
+            at: Instant::now(),
+            result: result.clone(),
+        });
+
+        Lookup { result, age: None }
+    }
+}
+
+static RECENT: Lazy<RecentPings> = Lazy::new(RecentPings::default);
+
+/// A result for the address that is still fresh, if there is one and no ping is in flight
+pub async fn fresh(addr: SocketAddr) -> Option<Lookup> {
+    RECENT.fresh(addr, ttl()).await
+}
+
+/// Reuses a fresh result for the address, otherwise runs `ping` once for everyone asking
+///
+/// Gives up with a timeout if another request's ping of the address takes longer than `wait`.
+pub async fn status_or_ping<F, Fut>(addr: SocketAddr, wait: Duration, ping: F) -> Lookup
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<ServerStatus, PingError>>,
+{
+    RECENT.status_or_ping(addr, ttl(), wait, ping).await
+}
+
+// Drops expired results so addresses that were only looked up once don't pile up
+fn prune(recent: &mut HashMap<SocketAddr, Slot>, ttl: Duration) {
+    recent.retain(|_, slot| {
+        // Someone is about to ping with it
+        if Arc::strong_count(slot) > 1 {
+            return true;
+        }
+
+        match slot.try_lock() {
+            Ok(slot) => slot.as_ref().is_some_and(|p| p.at.elapsed() < ttl),
+            Err(_) => true,
+        }
+    });
+}
+
+/// The outcome of the last status query of a saved server
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedStatus {
+    /// Unix time the server was queried at
+    pub checked: u64,
+    pub online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ServerStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Last status of every saved server, by saved address, however old it is
+static LAST_STATUS: Lazy<RwLock<HashMap<String, CachedStatus>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub async fn store(address: &str, result: Result<&ServerStatus, &PingError>) {
+    let checked = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let cached = match result {
+        Ok(status) => CachedStatus {
+            checked,
+            online: true,
+            latency_ms: status.latency.map(|l| l.as_millis() as u64),
+            status: Some(status.clone()),
+            error: None,
+        },
+        Err(err) => CachedStatus {
+            checked,
+            online: false,
+            latency_ms: None,
+            status: None,
+            error: Some(err.to_string()),
+        },
+    };
+
+    LAST_STATUS
+        .write()
+        .await
+        .insert(address.to_string(), cached);
+}
+
+pub async fn get(address: &str) -> Option<CachedStatus> {
+    LAST_STATUS.read().await.get(address).cloned()
+}
+
+pub async fn forget(address: &str) {
+    LAST_STATUS.write().await.remove(address);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use mcstatus::PingOptions;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const TTL: Duration = Duration::from_millis(200);
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    // A ping that counts how often it runs and takes a moment to fail
+    async fn counted(pings: &AtomicUsize) -> Result<ServerStatus, PingError> {
+        pings.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Err(PingError::Timeout)
+    }
+
+    #[tokio::test]
+    async fn waiting_requests_share_the_ping_in_flight() {
+        let recent = RecentPings::default();
+        let pings = AtomicUsize::new(0);
+
+        // Even with nothing kept afterwards, everyone already waiting gets the same result
+        let lookups = futures::future::join_all(
+            (0..5)
+                .map(|_| recent.status_or_ping(addr(1), Duration::ZERO, WAIT, || counted(&pings))),
+        )
+        .await;
+
+        assert_eq!(pings.load(Ordering::SeqCst), 1);
+        assert_eq!(lookups.iter().filter(|l| l.age.is_none()).count(), 1);
+
+        // Asking again afterwards pings again
+        recent
+            .status_or_ping(addr(1), Duration::ZERO, WAIT, || counted(&pings))
+            .await;
+        assert_eq!(pings.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn results_are_reused_until_they_expire() {
+        let recent = RecentPings::default();
+        let pings = AtomicUsize::new(0);
+
+        recent
+            .status_or_ping(addr(2), TTL, WAIT, || counted(&pings))
+            .await;
+        let reused = recent
+            .status_or_ping(addr(2), TTL, WAIT, || counted(&pings))
+            .await;
+        assert_eq!(pings.load(Ordering::SeqCst), 1);
+        assert!(reused.age.is_some());
+        assert!(recent.fresh(addr(2), TTL).await.is_some());
+
+        tokio::time::sleep(TTL).await;
+        assert!(recent.fresh(addr(2), TTL).await.is_none());
+
+        let pinged = recent
+            .status_or_ping(addr(2), TTL, WAIT, || counted(&pings))
+            .await;
+        assert_eq!(pings.load(Ordering::SeqCst), 2);
+        assert!(pinged.age.is_none());
+    }
+
+    #[tokio::test]
+    async fn failures_are_reused_like_successes() {
+        let recent = RecentPings::default();
+        let pings = AtomicUsize::new(0);
+
+        recent
+            .status_or_ping(addr(3), TTL, WAIT, || counted(&pings))
+            .await;
+        let reused = recent
+            .status_or_ping(addr(3), TTL, WAIT, || counted(&pings))
+            .await;
+
+        assert_eq!(pings.load(Ordering::SeqCst), 1);
+        assert!(matches!(*reused.result, Err(PingError::Timeout)));
+
+        // Different addresses don't share results
+        recent
+            .status_or_ping(addr(4), TTL, WAIT, || counted(&pings))
+            .await;
+        assert_eq!(pings.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn waiting_on_a_hanging_server_times_out() {
+        // Accepts connections and never answers them
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hanging = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let recent = RecentPings::default();
+        let timeout = Duration::from_millis(300);
+        let options = PingOptions {
+            timeout: Some(timeout),
+            ..PingOptions::default()
+        };
+        let ping = || mcstatus::ping_resolved(hanging, "localhost", &options);
+
+        let started = Instant::now();
+        let (first, second) =
+            tokio::join!(recent.status_or_ping(hanging, TTL, timeout, ping), async {
+                // Arrives while the first ping holds the slot
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                recent.status_or_ping(hanging, TTL, timeout, ping).await
+            },);
+
+        assert!(matches!(*first.result, Err(PingError::Timeout)));
+        assert!(second.result.is_err());
+        assert!(started.elapsed() < timeout * 2);
+    }
+}
@@ -0,0 +1,591 @@
+use std::{
+    env, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use mcstatus::ServerAddress;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serenity::utils::Color;
+
+use crate::{
+    cache,
+    network::{self, IpRange},
+};
+
+// Read when CONFIG_PATH isn't set, if it exists
+const DEFAULT_PATH: &str = "config.toml";
+
+/// Everything the bot can be configured with, from the config file and then the environment
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub discord: DiscordConfig,
+    pub storage: StorageConfig,
+    pub http: HttpConfig,
+    pub ping: PingConfig,
+    pub buttons: ButtonConfig,
+    pub statusip: StatusIpConfig,
+    pub network: NetworkConfig,
+    pub rate_limit: RateLimitConfig,
+    pub colors: ColorConfig,
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    pub token: Option<String>,
+    pub prefix: String,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> DiscordConfig {
+        DiscordConfig {
+            token: None,
+            prefix: String::from("~"),
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// File saved servers are kept in. Without one they're lost when the bot stops
+    pub path: Option<PathBuf>,
+    /// File each guild's settings, like its prefix, are kept in
+    pub settings_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub metrics_addr: Option<SocketAddr>,
+    pub api_addr: Option<SocketAddr>,
+}
+
+/// How servers are pinged, all in seconds
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PingConfig {
+    /// How long commands wait on each server they ping, connecting included
+    pub timeout: u64,
+    /// How long the poller waits on each server
+    pub poll_timeout: u64,
+    pub poll_interval: u64,
+    /// How long a status is reused for before the server is pinged again
+    pub cache_ttl: u64,
+}
+
+impl Default for PingConfig {
+    fn default() -> PingConfig {
+        PingConfig {
+            timeout: 5,
+            poll_timeout: 10,
+            poll_interval: 60,
+            cache_ttl: 30,
+        }
+    }
+}
+
+impl PingConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    pub fn poll_timeout(&self) -> Duration {
+        Duration::from_secs(self.poll_timeout)
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval)
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl)
+    }
+}
+
+/// How long buttons keep working, in seconds
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ButtonConfig {
+    /// How long the author has to confirm before the command is cancelled
+    pub confirm_timeout: u64,
+    /// How long page buttons keep working after the last time someone pressed one
+    pub page_timeout: u64,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> ButtonConfig {
+        ButtonConfig {
+            confirm_timeout: 30,
+            page_timeout: 120,
+        }
+    }
+}
+
+impl ButtonConfig {
+    pub fn confirm_timeout(&self) -> Duration {
+        Duration::from_secs(self.confirm_timeout)
+    }
+
+    pub fn page_timeout(&self) -> Duration {
+        Duration::from_secs(self.page_timeout)
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusIpConfig {
+    /// Hosts statusip may ping, like `mc.example.com` or `*.example.com`. Empty allows any
+    pub allow: Vec<String>,
+}
+
+impl StatusIpConfig {
+    /// Whether statusip may ping a host
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+
+        self.allow.is_empty()
+            || self.allow.iter().any(|pattern| {
+                let pattern = pattern.to_lowercase();
+                match pattern.strip_prefix("*.") {
+                    Some(domain) => host
+                        .strip_suffix(domain)
+                        .is_some_and(|sub| sub.ends_with('.')),
+                    None => host == pattern,
+                }
+            })
+    }
+}
+
+/// Which addresses the bot connects to, checked after looking up a server's host name
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Never connected to. Private, loopback and link-local ranges by default
+    pub deny: Vec<IpRange>,
+    /// Connected to even when they're in a denied range, like a LAN the servers are on
+    pub allow: Vec<IpRange>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> NetworkConfig {
+        NetworkConfig {
+            deny: network::default_deny(),
+            allow: Vec::new(),
+        }
+    }
+}
+
+/// How often each user can run commands
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Seconds the limits below count over
+    pub time_span: u64,
+    /// Uses of status, statusip and statusall, which ping servers
+    pub pings: u32,
+    /// Uses of every other command
+    pub commands: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            time_span: 60,
+            pings: 10,
+            commands: 30,
+        }
+    }
+}
+
+/// An embed colour, written as `#rrggbb` in the config file
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct EmbedColor(pub Color);
+
+impl TryFrom<String> for EmbedColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<EmbedColor, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(&hex);
+
+        match u32::from_str_radix(digits, 16) {
+            Ok(rgb) if digits.len() == 6 => Ok(EmbedColor(Color::new(rgb))),
+            _ => Err(format!("`{}` should be a colour like #00ff00", hex)),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    /// Connecting, waiting for confirmation, or only some servers online
+    pub pending: EmbedColor,
+    pub online: EmbedColor,
+    pub offline: EmbedColor,
+}
+
+impl Default for ColorConfig {
+    fn default() -> ColorConfig {
+        ColorConfig {
+            pending: EmbedColor(Color::from_rgb(255, 255, 0)),
+            online: EmbedColor(Color::from_rgb(0, 255, 0)),
+            offline: EmbedColor(Color::from_rgb(255, 0, 0)),
+        }
+    }
+}
+
+/// Why the config couldn't be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => {
+                write!(f, "Couldn't read config file {}: {}", path.display(), err)
+            }
+            ConfigError::Parse(path, err) => {
+                write!(f, "Config file {} is invalid: {}", path.display(), err)
+            }
+            ConfigError::Invalid(why) => write!(f, "Invalid config: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Parses an environment variable, naming it and the expected format when it's wrong
+fn parse_var<T: std::str::FromStr>(
+    name: &str,
+    value: &str,
+    expected: &str,
+) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| {
+        ConfigError::Invalid(format!("{} should be {}, not `{}`", name, expected, value))
+    })
+}
+
+impl Config {
+    /// Reads the config file from CONFIG_PATH or config.toml, applies the environment and checks it
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = match env::var_os("CONFIG_PATH") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(DEFAULT_PATH)).filter(|p| p.exists()),
+        };
+
+        let mut config = match path {
+            Some(path) => Config::read(&path)?,
+            None => Config::default(),
+        };
+
+        config.override_with(|name| env::var(name).ok())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Config, ConfigError> {
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Read(path.to_owned(), err))?;
+        toml::from_str(&text).map_err(|err| ConfigError::Parse(path.to_owned(), err))
+    }
+
+    /// Replaces settings with the environment variables that are set
+    pub fn override_with(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        if let Some(token) = var("DISCORD_TOKEN") {
+            self.discord.token = Some(token);
+        }
+        if let Some(prefix) = var("DISCORD_PREFIX") {
+            self.discord.prefix = prefix;
+        }
+        if let Some(path) = var("STORAGE_PATH") {
+            self.storage.path = Some(PathBuf::from(path));
+        }
+        if let Some(path) = var("SETTINGS_PATH") {
+            self.storage.settings_path = Some(PathBuf::from(path));
+        }
+
+        if let Some(addr) = var("METRICS_ADDR") {
+            self.http.metrics_addr = Some(parse_var(
+                "METRICS_ADDR",
+                &addr,
+                "an address like 0.0.0.0:9100",
+            )?);
+        }
+        if let Some(addr) = var("API_ADDR") {
+            self.http.api_addr = Some(parse_var(
+                "API_ADDR",
+                &addr,
+                "an address like 0.0.0.0:8080",
+            )?);
+        }
+
+        // METRICS_POLL_INTERVAL is the name from before the HTTP API also used the poller
+        let interval = var("POLL_INTERVAL")
+            .map(|secs| ("POLL_INTERVAL", secs))
+            .or_else(|| var("METRICS_POLL_INTERVAL").map(|secs| ("METRICS_POLL_INTERVAL", secs)));
+        if let Some((name, secs)) = interval {
+            self.ping.poll_interval = parse_var(name, &secs, "a number of seconds")?;
+        }
+        if let Some(secs) = var("STATUS_CACHE_TTL") {
+            self.ping.cache_ttl = parse_var("STATUS_CACHE_TTL", &secs, "a number of seconds")?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the settings make sense together
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |why: String| Err(ConfigError::Invalid(why));
+
+        if self.discord.token.as_deref().unwrap_or_default().is_empty() {
+            return invalid(String::from(
+                "there is no Discord token, set DISCORD_TOKEN or token under [discord]",
+            ));
+        }
+
+        if self.discord.prefix.is_empty() || self.discord.prefix.contains(char::is_whitespace) {
+            return invalid(String::from("the prefix can't be empty or contain spaces"));
+        }
+
+        if self.storage.path.is_some() && self.storage.path == self.storage.settings_path {
+            return invalid(String::from(
+                "path and settings_path under [storage] can't be the same file",
+            ));
+        }
+
+        if self.http.metrics_addr.is_some() && self.http.metrics_addr == self.http.api_addr {
+            return invalid(String::from(
+                "metrics_addr and api_addr can't be the same address",
+            ));
+        }
+
+        for (name, secs) in [
+            ("ping.timeout", self.ping.timeout),
+            ("ping.poll_timeout", self.ping.poll_timeout),
+            ("ping.poll_interval", self.ping.poll_interval),
+            ("buttons.confirm_timeout", self.buttons.confirm_timeout),
+            ("buttons.page_timeout", self.buttons.page_timeout),
+            ("rate_limit.time_span", self.rate_limit.time_span),
+        ] {
+            if secs == 0 {
+                return invalid(format!("{} must be at least 1 second", name));
+            }
+        }
+
+        if self.rate_limit.pings == 0 || self.rate_limit.commands == 0 {
+            return invalid(String::from(
+                "rate_limit.pings and rate_limit.commands must be at least 1",
+            ));
+        }
+
+        for pattern in &self.statusip.allow {
+            let host = pattern.strip_prefix("*.").unwrap_or(pattern);
+            match host.parse::<ServerAddress>() {
+                Ok(address) if !host.contains(':') || address.host == host => {}
+                _ => {
+                    return invalid(format!(
+                    "`{}` in statusip.allow should be a host like mc.example.com or *.example.com",
+                    pattern
+                ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+static CONFIG: Lazy<RwLock<Arc<Config>>> = Lazy::new(Default::default);
+
+/// The config currently in use
+pub fn get() -> Arc<Config> {
+    CONFIG
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Starts using a config
+pub fn set(config: Config) {
+    cache::set_ttl(config.ping.cache_ttl());
+    *CONFIG.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+}
+
+/// Loads the config again, keeping the settings that only take effect at startup
+pub fn reload() {
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(why) => {
+            error!("Keeping the current config. {}", why);
+            return;
+        }
+    };
+
+    let current = get();
+    if config.discord != current.discord
+        || config.storage != current.storage
+        || config.http != current.http
+        || config.rate_limit != current.rate_limit
+    {
+        warn!("Changes to [discord], [storage], [http] and [rate_limit] only take effect after a restart");
+    }
+    config.discord = current.discord.clone();
+    config.storage = current.storage.clone();
+    config.http = current.http.clone();
+    config.rate_limit = current.rate_limit.clone();
+
+    set(config);
+    info!("Reloaded config");
+}
+
+/// Reloads the config whenever the bot gets SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_hangup() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(why) => {
+            error!(
+                "Can't listen for SIGHUP, the config won't be reloaded: {}",
+                why
+            );
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        reload();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    fn with_token(text: &str) -> Config {
+        let mut config = parse(text);
+        config.discord.token = Some(String::from("token"));
+        config
+    }
+
+    #[test]
+    fn example_denies_the_default_ranges() {
+        let example = parse(include_str!("../config.example.toml"));
+        assert_eq!(example.network.deny, network::default_deny());
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let config = parse(
+            r##"
+            [discord]
+            token = "abc"
+            prefix = "!"
+
+            [storage]
+            path = "servers.json"
+
+            [http]
+            metrics_addr = "0.0.0.0:9100"
+
+            [ping]
+            timeout = 3
+            cache_ttl = 0
+
+            [statusip]
+            allow = ["*.example.com"]
+
+            [colors]
+            online = "#123456"
+            "##,
+        );
+
+        assert!(config.validate().is_ok());
+        assert_eq!(config.discord.prefix, "!");
+        assert_eq!(config.storage.path, Some(PathBuf::from("servers.json")));
+        assert_eq!(config.http.api_addr, None);
+        assert_eq!(config.ping.timeout(), Duration::from_secs(3));
+        assert_eq!(config.ping.poll_interval(), Duration::from_secs(60));
+        assert_eq!(config.colors.online.0, Color::new(0x123456));
+        assert_eq!(config.colors.offline.0, Color::from_rgb(255, 0, 0));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_values() {
+        assert!(toml::from_str::<Config>("[discord]\ntokn = \"abc\"").is_err());
+        assert!(toml::from_str::<Config>("[colors]\nonline = \"green\"").is_err());
+        assert!(toml::from_str::<Config>("[http]\napi_addr = \"localhost\"").is_err());
+
+        assert!(Config::default().validate().is_err());
+        assert!(with_token("[ping]\npoll_interval = 0").validate().is_err());
+        assert!(with_token("[discord]\nprefix = \"\"").validate().is_err());
+        assert!(with_token("[statusip]\nallow = [\"mc example\"]")
+            .validate()
+            .is_err());
+        assert!(
+            with_token("[http]\nmetrics_addr = \"0.0.0.0:80\"\napi_addr = \"0.0.0.0:80\"")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let env: HashMap<&str, &str> = [
+            ("DISCORD_PREFIX", "?"),
+            ("API_ADDR", "127.0.0.1:8080"),
+            ("METRICS_POLL_INTERVAL", "15"),
+        ]
+        .into();
+        let var = |name: &str| env.get(name).map(|v| v.to_string());
+
+        let mut config = with_token("[discord]\nprefix = \"!\"\n[ping]\npoll_interval = 30");
+        config.override_with(var).unwrap();
+        assert_eq!(config.discord.prefix, "?");
+        assert_eq!(
+            config.http.api_addr,
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(config.ping.poll_interval, 15);
+
+        let err = Config::default()
+            .override_with(|_| Some(String::from("soon")))
+            .unwrap_err();
+        assert!(err.to_string().contains("METRICS_ADDR"));
+    }
+
+    #[test]
+    fn statusip_allow_list_matches_hosts() {
+        let statusip = StatusIpConfig {
+            allow: vec![String::from("*.example.com"), String::from("1.2.3.4")],
+        };
+
+        assert!(statusip.allows("mc.Example.com"));
+        assert!(statusip.allows("1.2.3.4"));
+        assert!(!statusip.allows("example.com"));
+        assert!(!statusip.allows("badexample.com"));
+        assert!(StatusIpConfig::default().allows("anything.net"));
+    }
+}
@@ -0,0 +1,81 @@
+use serenity::{
+    client::Context,
+    model::{
+        channel::Message,
+        interactions::{message_component::ButtonStyle, InteractionResponseType},
+    },
+};
+
+use crate::config;
+
+/// Asks the author of a command to confirm it with a button, returning whether they did
+pub async fn confirm(
+    ctx: &Context,
+    msg: &Message,
+    question: &str,
+) -> Result<bool, serenity::Error> {
+    let mut prompt = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.color(config::get().colors.pending.0);
+                e.description(question);
+
+                e
+            });
+            m.components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id("confirm");
+                        b.label("Confirm");
+                        b.style(ButtonStyle::Danger)
+                    });
+                    r.create_button(|b| {
+                        b.custom_id("cancel");
+                        b.label("Cancel");
+                        b.style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    // Only the person who ran the command gets to answer
+    let answer = prompt
+        .await_component_interaction(ctx)
+        .author_id(msg.author.id)
+        .timeout(config::get().buttons.confirm_timeout())
+        .await;
+
+    let confirmed = matches!(&answer, Some(i) if i.data.custom_id == "confirm");
+
+    let outcome = match &answer {
+        Some(_) if confirmed => "Confirmed.",
+        Some(_) => "Cancelled.",
+        None => "Cancelled, nobody confirmed in time.",
+    };
+
+    match answer {
+        Some(interaction) => {
+            interaction
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage);
+                    r.interaction_response_data(|d| {
+                        d.create_embed(|e| e.description(format!("{}\n{}", question, outcome)));
+                        d.components(|c| c)
+                    })
+                })
+                .await?;
+        }
+        None => {
+            prompt
+                .edit(ctx, |m| {
+                    m.embed(|e| e.description(format!("{}\n{}", question, outcome)));
+                    m.components(|c| c)
+                })
+                .await?;
+        }
+    }
+
+    Ok(confirmed)
+}
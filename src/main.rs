@@ -1,47 +1,190 @@
-use once_cell::sync::Lazy;
 use serenity::http::AttachmentType;
-use serenity::model::id::GuildId;
-use serenity::prelude::RwLock;
 use serenity::utils::Color;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::env;
 use std::fmt::Display;
+use std::process;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
 
-use serde_json::{self, Value};
-
+use futures::StreamExt;
+use log::error;
+use mcstatus::{Mod, PingError, PingOptions, ServerAddress, ServerStatus};
+use serde::{Deserialize, Serialize};
 use serenity::async_trait;
+use serenity::builder::CreateEmbed;
 use serenity::client::{Client, Context, EventHandler};
 use serenity::framework::standard::{
-    macros::{command, group},
-    CommandResult, StandardFramework,
+    macros::{command, group, hook},
+    Args, CommandResult, DispatchError, Reason, StandardFramework,
 };
 use serenity::model::channel::Message;
-use tokio::net::TcpStream;
+use serenity::model::id::ChannelId;
+use serenity::model::interactions::InteractionResponseType;
+use serenity::model::misc::Mentionable;
 
 #[group]
 #[commands(
-    ping, help, add, remove, setactive, status, statusip, removeall, servers
+    ping,
+    help,
+    add,
+    remove,
+    setactive,
+    status,
+    statusip,
+    removeall,
+    servers,
+    statusall,
+    apitoken,
+    permissions,
+    rename,
+    setip,
+    clearactive,
+    tag,
+    untag,
+    export,
+    import,
+    setprefix
 )]
 
 struct General;
 
+mod api;
+mod args;
+mod backup;
+mod cache;
+mod config;
+mod confirm;
+mod metrics;
+mod network;
+mod pages;
+mod permissions;
+mod poller;
+mod settings;
+mod storage;
+use backup::{Export, ImportMode};
+use config::Config;
+use confirm::confirm;
+use metrics::METRICS;
+use pages::ListPages;
+use permissions::{Access, MANAGE_CHECK};
+use settings::SETTINGS;
+use storage::{GuildServers, Scope, SERVERS};
+
 struct Handler;
 
 #[async_trait]
 impl EventHandler for Handler {}
 
+// Counts every command run, and any Discord API errors it ran into
+#[hook]
+async fn after(_ctx: &Context, _msg: &Message, command_name: &str, result: CommandResult) {
+    METRICS.record_command(command_name);
+
+    if let Err(why) = result {
+        if why.downcast_ref::<serenity::Error>().is_some() {
+            METRICS.record_discord_error();
+        }
+        error!("Command '{}' returned error {:?}", command_name, why);
+    }
+}
+
+// Tells people why a command they ran was refused
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
+    let reason = match error {
+        DispatchError::CheckFailed(_, Reason::User(reason)) => reason,
+        DispatchError::LackingPermissions(perms) => {
+            format!("You need the {} permission to use this command.", perms)
+        }
+        DispatchError::OnlyForGuilds => String::from("This command only works in a server."),
+        // Only answer the first time, so the reply can't be used to spam the channel
+        DispatchError::Ratelimited(info) if info.is_first_try => format!(
+            "Slow down! Try again in {} seconds.",
+            info.rate_limit.as_secs().max(1)
+        ),
+        _ => return,
+    };
+
+    if let Err(why) = msg.reply(ctx, reason).await {
+        METRICS.record_discord_error();
+        error!("Failed to send dispatch error: {:?}", why);
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    let prefix = env::var("DISCORD_PREFIX").expect("prefix");
+    env_logger::init();
+
+    // Settings come from config.toml, or the file in CONFIG_PATH, and then the environment
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(why) => {
+            eprintln!("{}", why);
+            process::exit(1);
+        }
+    };
+    config::set(config.clone());
+
+    #[cfg(unix)]
+    tokio::spawn(config::reload_on_hangup());
+
+    // Keep saved servers between restarts if there's somewhere to keep them
+    if let Some(path) = config.storage.path.clone() {
+        if let Err(why) = SERVERS.load(&path) {
+            eprintln!(
+                "Couldn't load saved servers from {}: {}",
+                path.display(),
+                why
+            );
+            process::exit(1);
+        }
 
+        tokio::spawn(async move { SERVERS.persist(&path).await });
+    }
+
+    if let Some(path) = config.storage.settings_path.clone() {
+        if let Err(why) = SETTINGS.load(&path) {
+            eprintln!("Couldn't load settings from {}: {}", path.display(), why);
+            process::exit(1);
+        }
+
+        tokio::spawn(async move { SETTINGS.persist(&path).await });
+    }
+
+    // Optionally serve Prometheus metrics and the HTTP API
+    if let Some(addr) = config.http.metrics_addr {
+        tokio::spawn(metrics::serve(addr));
+    }
+
+    if let Some(addr) = config.http.api_addr {
+        tokio::spawn(api::serve(addr));
+    }
+
+    // Poll saved servers to keep both of them current
+    if config.http.metrics_addr.is_some() || config.http.api_addr.is_some() {
+        tokio::spawn(poller::poll_servers());
+    }
+
+    // Each guild can have its own prefix, so there's no fixed one
+    let limits = config.rate_limit.clone();
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix(&prefix)) // set the bot's prefix to "~"
+        .configure(|c| c.prefix("").dynamic_prefix(settings::prefix_hook))
+        // Limits are per user, with pinging servers limited separately from everything else
+        .bucket("pings", |b| {
+            b.time_span(limits.time_span).limit(limits.pings)
+        })
+        .await
+        .bucket("commands", |b| {
+            b.time_span(limits.time_span).limit(limits.commands)
+        })
+        .await
+        .after(after)
+        .on_dispatch_error(dispatch_error)
         .group(&GENERAL_GROUP);
 
-    // Login with a bot token from the environment
-    let token = env::var("DISCORD_TOKEN").expect("token");
+    // Login with the bot token, which validation made sure is there
+    let token = config.discord.token.clone().unwrap_or_default();
     let mut client = Client::builder(token)
         .event_handler(Handler)
         .framework(framework)
@@ -55,43 +198,62 @@ async fn main() {
 }
 
 #[command]
+#[bucket = "commands"]
 async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
     msg.reply(ctx, "Pong!").await?;
 
     Ok(())
 }
 
-mod network;
-
 // Store a Minecraft server to get status of
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct MCServer {
+    // Stays the same when the server is renamed or moved. 0 until it's saved
+    pub id: u64,
     pub ip: String,
     pub name: Option<String>,
+    // Groups the server belongs to, like the network it's part of
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl MCServer {
-    pub fn new(ip: String, name: Option<String>) -> MCServer {
-        let mut ip = ip;
-
-        // Check IP contains port, if not then add default Minecraft port
-        let mut stuff = ip.split(":");
-        stuff.next();
+    pub fn new(address: &ServerAddress, name: Option<String>) -> MCServer {
+        // Always includes the port, with IPv6 addresses in brackets
+        MCServer {
+            id: 0,
+            ip: address.to_string(),
+            name,
+            tags: Vec::new(),
+        }
+    }
 
-        let port = stuff.next();
+    // Tags are matched ignoring case, like names
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|t| t.to_lowercase() == tag.to_lowercase())
+    }
 
-        if port.is_none() {
-            ip.push_str(":25565");
-        }
+    // Host the server was saved with, sent to it in the handshake
+    pub fn host(&self) -> String {
+        origin(&self.ip)
+    }
 
-        MCServer { ip, name }
+    // Names are matched ignoring case, so "Survival" and "survival" are the same server
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name
+            .as_ref()
+            .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
     }
 }
 
-#[derive(Debug, Clone)]
-struct GuildServers {
-    pub active: usize,
-    pub servers: Vec<MCServer>,
+// Host of a saved address, which was checked when it was saved
+pub fn origin(addr: &str) -> String {
+    match addr.parse::<ServerAddress>() {
+        Ok(address) => address.host,
+        Err(_) => addr.to_string(),
+    }
 }
 
 impl Display for MCServer {
@@ -107,48 +269,39 @@ impl Display for MCServer {
     }
 }
 
-// Stores minecraft servers for each discord guild
-static SERVERS: Lazy<Arc<RwLock<HashMap<GuildId, GuildServers>>>> =
-    Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
-
-// Checks if a discord guild has a record in SERVERS, if not then it creates a new one
-async fn check_guild_server_exists(id: &GuildId) {
-    {
-        let map = SERVERS.read().await;
-
-        if map.contains_key(id) {
-            return;
-        }
-    }
-
-    let mut map = SERVERS.write().await;
-
-    map.insert(
-        *id,
-        GuildServers {
-            active: 0,
-            servers: Vec::new(),
-        },
-    );
-}
-
 // Prints commands and how to use them into discord
 #[command]
+#[bucket = "commands"]
 async fn help(ctx: &Context, msg: &Message) -> CommandResult {
     msg.channel_id.send_message(&ctx.http, |m| {
         m.embed(|e| {
 
             e.title("Commands");
+            e.description("Put quotes around names with spaces, like `add \"My Server\" mc.example.com`.\nIn direct messages these commands use your own personal list of servers.");
 
             e.field("help", "Open this menu", false);
             e.field("add <ServerName> <ServerIP>", "Adds a Minecraft server with a name to the list", false);
             e.field("remove <ServerName>", "Removes a server from the list", false);
             e.field("removeall", "Removes all servers from the list", false);
+            e.field("rename <ServerName> <NewName>", "Renames a saved server", false);
+            e.field("setip <ServerName> <ServerIP>", "Changes the address of a saved server", false);
             e.field("setactive <ServerName>", "Sets a server as the active one so that running `status` automatically uses that one", false);
-            e.field("servers", "Lists all server currently in the list", false);
+            e.field("setactive <ServerName> here", "Sets the active server for just this channel", false);
+            e.field("clearactive", "Makes this channel use the default active server again", false);
+            e.field("tag <ServerName> <Tag...>", "Adds tags to a saved server, to group servers like the network they're part of", false);
+            e.field("untag <ServerName> <Tag...>", "Removes tags from a saved server", false);
+            e.field("servers [Tag]", "Lists all server currently in the list sorted by tag, or only those with a tag", false);
             e.field("status", "Gets the status of the Minecraft server currently set as active", false);
             e.field("status <ServerName>", "Gets the status of the saved Minecraft server with that name", false);
+            e.field("status group:<Tag>", "Gets the combined status of every saved Minecraft server with that tag", false);
+            e.field("statusall", "Gets a short status of every saved Minecraft server at once", false);
             e.field("statusip <ServerIP>", "Gets the status of the Minecraft server at that IP, it does not need to be saved for this to work", false);
+            e.field("permissions", "Lists who may use the commands that change the server list (needs Manage Server)", false);
+            e.field("permissions <Command> <everyone|manage|roles @Role...>", "Changes who may use one of those commands (needs Manage Server)", false);
+            e.field("export", "Sends a file with every saved server and which ones are active", false);
+            e.field("import <merge|replace>", "Imports servers from an attached file made by `export`, showing what would change first", false);
+            e.field("setprefix [Prefix]", "Changes the prefix of commands in this server, or goes back to the default without one (needs Manage Server)", false);
+            e.field("apitoken", "Sends you a new token for this server's HTTP API, replacing the old one (needs Manage Server)", false);
 
         e});
         m}).await?;
@@ -158,236 +311,257 @@ async fn help(ctx: &Context, msg: &Message) -> CommandResult {
 
 // Adds a Minecraft server with a name to this guild's list
 #[command]
-async fn add(ctx: &Context, msg: &Message) -> CommandResult {
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> <ServerIP>")]
+#[example("survival mc.example.com")]
+#[example("\"Modded Server\" 192.168.1.10:25566")]
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     // Checks command has all the args
-    let mut stuff = msg.content.split(" ");
-    stuff.next();
+    if args.len() != 2 {
+        return args::usage_error(ctx, msg, &ADD_COMMAND_OPTIONS).await;
+    }
 
-    let name = stuff.next();
-    let ip = stuff.next();
+    let name = args::next(&mut args).unwrap_or_default();
+    let ip = args::next(&mut args).unwrap_or_default();
 
-    if name.is_none() || ip.is_none() {
-        msg.reply(
-            ctx,
-            "Improper command uages. Proper use:\nadd <ServerName> <ServerIP>",
-        )
-        .await?;
+    if let Err(why) = args::validate_name(&name) {
+        msg.reply(ctx, why).await?;
         return Ok(());
     }
 
-    let name = name.unwrap().to_string();
-    let ip = ip.unwrap().to_string();
-
-    let id = &msg.guild_id.unwrap();
+    let address: ServerAddress = match ip.parse() {
+        Ok(address) => address,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
 
-    let mut servs = SERVERS.write().await;
+    let scope = Scope::of(msg);
 
-    // Gets guild record and adds server
-    match servs.get_mut(id) {
-        Some(gs) => {
-            gs.servers
-                .push(MCServer::new(ip.clone(), Some(name.clone())));
-        }
-        // Creates a guild record with the new server if there was no record
-        None => {
-            let gs = GuildServers {
-                active: 0,
-                servers: vec![MCServer::new(ip.clone(), Some(name.clone()))],
-            };
-            servs.insert(*id, gs);
+    // Adds the server to the guild's record, unless the name is taken
+    let added = SERVERS.update(scope, |gs| {
+        if gs.find(&name).is_some() {
+            return false;
         }
+
+        gs.add(MCServer::new(&address, Some(name.clone())));
+        true
+    });
+
+    if !added {
+        msg.reply(
+            ctx,
+            &format!("There is already a saved server named {}", name),
+        )
+        .await?;
+        return Ok(());
     }
 
     // Notify discord user
-    msg.reply(ctx, &format!("Added {} ({}) to the server list.", name, ip))
-        .await?;
+    msg.reply(
+        ctx,
+        &format!("Added {} ({}) to the server list.", name, address),
+    )
+    .await?;
 
     Ok(())
 }
 
 // Removes a server from this guild's record
 #[command]
-async fn remove(ctx: &Context, msg: &Message) -> CommandResult {
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName>")]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     // Checks for correct command args
-    let mut stuff = msg.content.split(" ");
-    stuff.next();
+    if args.len() != 1 {
+        return args::usage_error(ctx, msg, &REMOVE_COMMAND_OPTIONS).await;
+    }
+
+    let name = args::next(&mut args).unwrap_or_default();
+    let name = name.as_str();
+
+    let scope = Scope::of(msg);
 
-    let name = stuff.next();
+    let exists = SERVERS.read(scope, |gs| gs.find(name).is_some());
 
-    if name.is_none() {
+    if !exists {
         msg.reply(
             ctx,
-            "Improper command uages. Proper use:\nremove <ServerName>",
+            &format!("There is no saved server with name: {}", name),
         )
         .await?;
         return Ok(());
     }
 
-    let name = name.unwrap();
-
-    let id = &msg.guild_id.unwrap();
-
-    check_guild_server_exists(id).await;
-
-    match SERVERS.write().await.get_mut(id) {
-        Some(gs) => {
-            // No servers
-            if gs.servers.is_empty() {
-                msg.channel_id
-                    .send_message(&ctx.http, |m| {
-                        m.content("There are no saved servers");
-                        m
-                    })
-                    .await
-                    .unwrap();
-                return Ok(());
-            }
-
-            let mut ind: Option<usize> = None;
-
-            // Looks for the servers the user is trying to remove and saves which index it's at
-            for (i, s) in gs.servers.iter().enumerate() {
-                if s.name.as_ref().unwrap() == name {
-                    ind = Some(i);
-                    break;
-                }
-            }
-
-            // Notify discord user and stop if that server doesn't exist
-            if ind.is_none() {
-                msg.reply(
-                    ctx,
-                    &format!("There is no saved server with name: {}", name),
-                )
-                .await?;
-                return Ok(());
-            }
-
-            let ind = ind.unwrap();
-
-            // Change active index if removing the server would affect that
-            if gs.active == ind {
-                gs.active = 0;
-            } else if gs.active > ind {
-                gs.active -= 1;
-            }
+    if !confirm(ctx, msg, &format!("Remove {} from the server list?", name)).await? {
+        return Ok(());
+    }
 
-            let s = gs.servers.remove(ind);
+    // Looks the server up again, in case it changed while waiting for confirmation
+    let removed = SERVERS.update(scope, |gs| {
+        let ind = gs.find(name)?;
+        Some(gs.remove(ind))
+    });
 
-            msg.reply(ctx, &format!("Removed {}", s)).await?;
-        }
+    match removed {
+        Some(s) => msg.reply(ctx, &format!("Removed {}", s)).await?,
         None => {
-            println!("This guild does not have a record.");
+            msg.reply(
+                ctx,
+                &format!("There is no saved server with name: {}", name),
+            )
+            .await?
         }
-    }
+    };
 
     Ok(())
 }
 
 // Sets the active server to the one the discord user specifies
 #[command]
-async fn setactive(ctx: &Context, msg: &Message) -> CommandResult {
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> [here]")]
+async fn setactive(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     // Check correct command args
-    let mut stuff = msg.content.split(" ");
-    stuff.next();
+    if args.is_empty() || args.len() > 2 {
+        return args::usage_error(ctx, msg, &SETACTIVE_COMMAND_OPTIONS).await;
+    }
+
+    let name = args::next(&mut args).unwrap_or_default();
+
+    // Only set it for this channel if asked to
+    let here = match args::next(&mut args).as_deref() {
+        None => false,
+        Some("here") => true,
+        Some(_) => return args::usage_error(ctx, msg, &SETACTIVE_COMMAND_OPTIONS).await,
+    };
 
-    let name = stuff.next();
+    let scope = Scope::of(msg);
 
-    if name.is_none() {
+    if here && msg.guild_id.is_none() {
         msg.reply(
             ctx,
-            "Improper command uages. Proper use:\nsetactive <ServerName>",
+            "Channels can only have their own active server in a server.",
         )
         .await?;
         return Ok(());
     }
 
-    let name = name.unwrap().to_string();
-
-    let id = &msg.guild_id.unwrap();
-
-    check_guild_server_exists(id).await;
-
-    let mut servs = SERVERS.write().await;
-
-    match servs.get_mut(id) {
-        Some(gs) => {
-            // Finds server the user specified
-            let mut ind: Option<usize> = None;
-
-            for (i, s) in gs.servers.iter().enumerate() {
-                if s.name.as_ref().unwrap() == &name {
-                    ind = Some(i);
-                    break;
-                }
-            }
-
-            // Alert user and stop if it doesn't exist
-            if ind.is_none() {
-                msg.reply(ctx, &format!("No saved server with name {}", name))
-                    .await?;
-                return Ok(());
-            }
-
-            let ind = ind.unwrap();
-
-            gs.active = ind;
+    // Finds server the user specified and makes it active
+    let active = SERVERS.update(scope, |gs| {
+        let serv = gs.servers[gs.find(&name)?].clone();
+        if here {
+            gs.channels.insert(msg.channel_id, serv.id);
+        } else {
+            gs.active = Some(serv.id);
+        }
+        Some(serv)
+    });
 
+    match active {
+        Some(s) if here => {
             msg.reply(
                 ctx,
-                &format!("Set active server to {}", gs.servers[gs.active]),
+                &format!("Set active server in {} to {}", msg.channel_id.mention(), s),
             )
-            .await?;
+            .await?
         }
+        Some(s) => {
+            msg.reply(ctx, &format!("Set active server to {}", s))
+                .await?
+        }
+        // Alert user if it doesn't exist
         None => {
-            println!("This guild does not have a record.");
+            msg.reply(ctx, &format!("No saved server with name {}", name))
+                .await?
         }
-    }
+    };
+
+    Ok(())
+}
+
+// Makes this channel use the guild's active server again
+#[command]
+#[bucket = "commands"]
+#[checks(Manage)]
+#[only_in(guilds)]
+async fn clearactive(ctx: &Context, msg: &Message) -> CommandResult {
+    let scope = Scope::of(msg);
+
+    let fallback = SERVERS.update(scope, |gs| {
+        gs.channels.remove(&msg.channel_id)?;
+        Some(gs.active_server().cloned())
+    });
+
+    let reply = match fallback {
+        Some(Some(s)) => format!("This channel now uses the default active server, {}", s),
+        Some(None) => String::from("This channel now uses the default active server"),
+        None => String::from("This channel doesn't have its own active server"),
+    };
+
+    msg.reply(ctx, reply).await?;
 
     Ok(())
 }
 
 // Gets the status of the Minecraft server specified or the active Minecraft server
 #[command]
-async fn status(ctx: &Context, msg: &Message) -> CommandResult {
+#[bucket = "pings"]
+#[usage("[ServerName|group:<Tag>]")]
+async fn status(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     // Get command args
-    let mut stuff = msg.content.split(" ");
-    stuff.next();
-
-    let name = stuff.next();
+    if args.len() > 1 {
+        return args::usage_error(ctx, msg, &STATUS_COMMAND_OPTIONS).await;
+    }
 
-    let id = &msg.guild_id.unwrap();
+    let name = args::next(&mut args);
 
-    check_guild_server_exists(id).await;
+    let scope = Scope::of(msg);
 
-    match SERVERS.read().await.get(id) {
-        Some(gs) => {
-            if gs.servers.is_empty() {
-                msg.reply(ctx, "There are no saved servers").await?;
-                return Ok(());
-            }
+    if let Some(tag) = name.as_deref().and_then(|n| n.strip_prefix("group:")) {
+        if tag.is_empty() {
+            return args::usage_error(ctx, msg, &STATUS_COMMAND_OPTIONS).await;
+        }
 
-            // Check if server was provided or to use active server
-            match name {
-                None => {
-                    get_status(ctx, msg, &gs.servers[gs.active]).await?;
-                }
-                Some(name) => {
-                    // Searches server list for specified server
-                    for s in gs.servers.iter() {
-                        if s.name.as_ref().unwrap() == name {
-                            get_status(ctx, msg, s).await?;
-                            return Ok(());
-                        }
-                    }
+        return group_status(ctx, msg, tag).await;
+    }
 
-                    msg.reply(ctx, &format!("There is no saved server with name {}", name))
-                        .await?;
-                }
-            }
+    // Copy the server out so nothing is locked while it's pinged.
+    // Check if server was provided or to use active server
+    let (empty, serv) = SERVERS.read(scope, |gs| {
+        let serv = match &name {
+            None => gs.active_in(msg.channel_id).cloned(),
+            Some(name) => gs.find(name).map(|i| gs.servers[i].clone()),
+        };
+
+        (gs.servers.is_empty(), serv)
+    });
+
+    match (serv, name) {
+        (Some(serv), _) => get_status(ctx, msg, &serv).await?,
+        _ if empty => {
+            msg.reply(ctx, "There are no saved servers").await?;
         }
-        None => {
-            println!("This Guild has no record.");
+        (None, None) => {
+            msg.reply(
+                ctx,
+                "There is no active server. Set one with `setactive <ServerName>` or give a server name",
+            )
+            .await?;
+        }
+        (None, name) => {
+            msg.reply(
+                ctx,
+                &format!(
+                    "There is no saved server with name {}",
+                    name.unwrap_or_default()
+                ),
+            )
+            .await?;
         }
     }
 
@@ -396,398 +570,1002 @@ async fn status(ctx: &Context, msg: &Message) -> CommandResult {
 
 // Gets status of minecraft server at provided address
 #[command]
-async fn statusip(ctx: &Context, msg: &Message) -> CommandResult {
+#[bucket = "pings"]
+#[usage("<ServerIP>")]
+async fn statusip(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     // Check for proper command args
-    let mut stuff = msg.content.split(" ");
-    stuff.next();
+    if args.len() != 1 {
+        return args::usage_error(ctx, msg, &STATUSIP_COMMAND_OPTIONS).await;
+    }
 
-    let ip = stuff.next();
+    let address: ServerAddress = match args::next(&mut args).unwrap_or_default().parse() {
+        Ok(address) => address,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
 
-    if ip.is_none() {
+    if !config::get().statusip.allows(&address.host) {
         msg.reply(
             ctx,
-            "Improper command uages. Proper use:\nstatusip <ServerIP>",
+            format!("statusip can't be used for {} on this bot", address.host),
         )
         .await?;
         return Ok(());
     }
 
-    let ip = ip.unwrap();
-
-    get_status(ctx, msg, &MCServer::new(ip.to_string(), None)).await?;
+    get_status(ctx, msg, &MCServer::new(&address, None)).await?;
 
     Ok(())
 }
 
-// Removes all servers from list
+// Renames a saved server, keeping everything else about it
 #[command]
-async fn removeall(ctx: &Context, msg: &Message) -> CommandResult {
-    let id = &msg.guild_id.unwrap();
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> <NewName>")]
+async fn rename(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() != 2 {
+        return args::usage_error(ctx, msg, &RENAME_COMMAND_OPTIONS).await;
+    }
 
-    check_guild_server_exists(id).await;
+    let name = args::next(&mut args).unwrap_or_default();
+    let new_name = args::next(&mut args).unwrap_or_default();
+
+    if let Err(why) = args::validate_name(&new_name) {
+        msg.reply(ctx, why).await?;
+        return Ok(());
+    }
 
-    match SERVERS.write().await.get_mut(id) {
-        Some(gs) => {
-            gs.active = 0;
-            gs.servers.clear();
+    let scope = Scope::of(msg);
 
-            msg.reply(ctx, "All servers have been removed!").await?;
+    let reply = SERVERS.update(scope, |gs| match (gs.find(&name), gs.find(&new_name)) {
+        (None, _) => format!("There is no saved server with name: {}", name),
+        // Changing only the case of a name finds the server itself
+        (Some(i), Some(taken)) if taken != i => {
+            format!("There is already a saved server named {}", new_name)
         }
-        None => {
-            println!("This guild does not have a record.");
+        (Some(i), _) => {
+            gs.servers[i].name = Some(new_name.clone());
+            format!("Renamed {} to {}", name, new_name)
         }
-    }
+    });
+
+    msg.reply(ctx, reply).await?;
 
     Ok(())
 }
 
-// Prints all saved servers for this guild in Discord
+// Changes the address of a saved server, keeping everything else about it
 #[command]
-async fn servers(ctx: &Context, msg: &Message) -> CommandResult {
-    let id = &msg.guild_id.unwrap();
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> <ServerIP>")]
+async fn setip(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() != 2 {
+        return args::usage_error(ctx, msg, &SETIP_COMMAND_OPTIONS).await;
+    }
 
-    check_guild_server_exists(id).await;
+    let name = args::next(&mut args).unwrap_or_default();
 
-    match SERVERS.read().await.get(id) {
-        Some(gs) => {
-            if gs.servers.is_empty() {
-                msg.reply(ctx, "There are no saved servers").await?;
-                return Ok(());
-            }
+    let address: ServerAddress = match args::next(&mut args).unwrap_or_default().parse() {
+        Ok(address) => address,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
 
-            // Create message
-            msg.channel_id
-                .send_message(&ctx.http, |m| {
-                    m.embed(|e| {
-                        e.title("Servers");
+    let scope = Scope::of(msg);
 
-                        e.field("Active", gs.servers[gs.active].to_string(), false);
+    let reply = SERVERS.update(scope, |gs| match gs.find(&name) {
+        Some(i) => {
+            gs.servers[i].ip = address.to_string();
+            format!("Changed the address of {} to {}", name, address)
+        }
+        None => format!("There is no saved server with name: {}", name),
+    });
 
-                        let mut servers = String::new();
+    msg.reply(ctx, reply).await?;
 
-                        for s in &gs.servers {
-                            servers.push_str(&format!("{}\n", s));
-                        }
+    Ok(())
+}
 
-                        e.field("Saved", servers, false);
+// Adds tags to a saved server
+#[command]
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> <Tag...>")]
+async fn tag(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() < 2 {
+        return args::usage_error(ctx, msg, &TAG_COMMAND_OPTIONS).await;
+    }
 
-                        e
-                    });
-                    m
-                })
-                .await
-                .unwrap();
+    let name = args::next(&mut args).unwrap_or_default();
+    let mut tags = Vec::new();
+    while let Some(tag) = args::next(&mut args) {
+        if let Err(why) = args::validate_tag(&tag) {
+            msg.reply(ctx, why).await?;
+            return Ok(());
         }
-        None => {
-            println!("This guild does not have a record.");
+        tags.push(tag);
+    }
+
+    let scope = Scope::of(msg);
+
+    let reply = SERVERS.update(scope, |gs| match gs.find(&name) {
+        Some(i) => {
+            let serv = &mut gs.servers[i];
+            for tag in tags {
+                if !serv.has_tag(&tag) {
+                    serv.tags.push(tag);
+                }
+            }
+            serv.tags.sort_by_key(|t| t.to_lowercase());
+
+            format!("{} is now tagged {}", name, serv.tags.join(", "))
         }
+        None => format!("There is no saved server with name: {}", name),
+    });
+
+    msg.reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+// Removes tags from a saved server
+#[command]
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<ServerName> <Tag...>")]
+async fn untag(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() < 2 {
+        return args::usage_error(ctx, msg, &UNTAG_COMMAND_OPTIONS).await;
+    }
+
+    let name = args::next(&mut args).unwrap_or_default();
+    let mut tags = Vec::new();
+    while let Some(tag) = args::next(&mut args) {
+        tags.push(tag.to_lowercase());
     }
 
+    let scope = Scope::of(msg);
+
+    let reply = SERVERS.update(scope, |gs| match gs.find(&name) {
+        Some(i) => {
+            let serv = &mut gs.servers[i];
+            serv.tags.retain(|t| !tags.contains(&t.to_lowercase()));
+
+            if serv.tags.is_empty() {
+                format!("{} has no tags now", name)
+            } else {
+                format!("{} is now tagged {}", name, serv.tags.join(", "))
+            }
+        }
+        None => format!("There is no saved server with name: {}", name),
+    });
+
+    msg.reply(ctx, reply).await?;
+
     Ok(())
 }
 
-// Gets and prints the status of a Minecraft server
-async fn get_status(ctx: &Context, msg: &Message, serv: &MCServer) -> CommandResult {
-    // Send discord message saying it's connecting
-    let mut resp = msg
-        .channel_id
-        .send_message(&ctx.http, |m| {
-            m.embed(|e| {
-                match &serv.name {
-                    Some(nam) => {
-                        e.title(&format!("{} ({})", nam, &serv.ip));
-                    }
-                    None => {
-                        e.title(&serv.ip);
-                    }
-                }
-                e.color(Color::from_rgb(255, 255, 0));
-                e.description("Connecting to server...");
+// Largest file import reads
+const MAX_IMPORT_BYTES: u64 = 1024 * 1024;
 
-                e
+// Sends a file with this guild's servers, which import can read into any guild
+#[command]
+#[bucket = "commands"]
+async fn export(ctx: &Context, msg: &Message) -> CommandResult {
+    let scope = Scope::of(msg);
+
+    let export = SERVERS.read(scope, Export::of);
+
+    if export.servers.is_empty() {
+        msg.reply(ctx, "There are no saved servers").await?;
+        return Ok(());
+    }
+
+    let data = serde_json::to_vec_pretty(&export)?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(format!("Exported {} servers.", export.servers.len()));
+            m.add_file(AttachmentType::Bytes {
+                data: Cow::from(data),
+                filename: String::from("servers.json"),
             });
 
             m
         })
         .await?;
 
-    // Connect to server
-    match TcpStream::connect(&serv.ip).await {
-        Ok(mut stream) => {
-            // Edit connecting message to connected if it connects
-            resp.edit(&ctx.http, |m| {
-                m.embed(|e| {
-                    match &serv.name {
-                        Some(nam) => {
-                            e.title(&format!("{} ({})", nam, &serv.ip));
-                        }
-                        None => {
-                            e.title(&serv.ip);
-                        }
-                    }
-                    e.color(Color::from_rgb(0, 255, 0));
-                    e.description("Connected!");
+    Ok(())
+}
 
-                    e
-                });
+// Imports servers from a file made by export
+#[command]
+#[bucket = "commands"]
+#[checks(Manage)]
+#[usage("<merge|replace> (with the exported file attached)")]
+async fn import(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() != 1 {
+        return args::usage_error(ctx, msg, &IMPORT_COMMAND_OPTIONS).await;
+    }
 
-                m
-            })
-            .await?;
+    let mode = match args::next(&mut args)
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "merge" => ImportMode::Merge,
+        "replace" => ImportMode::Replace,
+        _ => return args::usage_error(ctx, msg, &IMPORT_COMMAND_OPTIONS).await,
+    };
 
-            // Get server status
-            match network::status(&mut stream).await {
-                Ok(response) => {
-                    let url = String::from("favicon.png");
-                    let mut icon: Option<Vec<u8>> = None;
-
-                    // Delete connected message as we have gotten the status
-                    resp.delete(ctx).await?;
-
-                    // Create status message in discord
-                    msg.channel_id
-                        .send_message(&ctx.http, |m| {
-                            // Creat message embed
-                            m.embed(|e| {
-                                // Title
-                                match &serv.name {
-                                    Some(nam) => {
-                                        e.title(&format!("{} ({})", nam, &serv.ip));
-                                    }
-                                    None => {
-                                        e.title(&serv.ip);
-                                    }
-                                }
-
-                                // Extract JSON
-                                match serde_json::from_str::<Value>(&response.response.0) {
-                                    Ok(json) => {
-                                        if let Value::Object(map) = json {
-                                            e.color(Color::from_rgb(0, 255, 0));
-
-                                            // MOTD
-                                            if let Some(Value::Object(description)) =
-                                                map.get("description")
-                                            {
-                                                if let Some(Value::String(motd)) =
-                                                    description.get("text")
-                                                {
-                                                    e.description(&motd);
-                                                }
-                                            }
-
-                                            // Favicon
-                                            if let Some(Value::String(favicon)) = map.get("favicon")
-                                            {
-                                                match base64::decode(
-                                                    &(favicon.replace("\n", ""))[22..],
-                                                ) {
-                                                    Ok(bytes) => {
-                                                        icon = Some(bytes);
-
-                                                        e.thumbnail(&format!(
-                                                            "attachment://{}",
-                                                            url
-                                                        ));
-                                                    }
-                                                    Err(err) => {
-                                                        e.description(&format!(
-                                                            "Failed to decode favicon: {}",
-                                                            err
-                                                        ));
-                                                    }
-                                                }
-                                            }
-
-                                            // Version number
-                                            if let Some(Value::Object(version)) = map.get("version")
-                                            {
-                                                if let Some(Value::String(ver_num)) =
-                                                    version.get("name")
-                                                {
-                                                    e.field("Version", ver_num, false);
-                                                }
-                                            }
-
-                                            // Players
-                                            if let Some(Value::Object(players)) = map.get("players")
-                                            {
-                                                if let Some(Value::Number(max)) = players.get("max")
-                                                {
-                                                    if let Some(Value::Number(online)) =
-                                                        players.get("online")
-                                                    {
-                                                        let mut playing =
-                                                            String::from("No players online.");
-
-                                                        // List players online
-                                                        if let Some(Value::Array(sample)) =
-                                                            players.get("sample")
-                                                        {
-                                                            for (i, p) in sample.iter().enumerate()
-                                                            {
-                                                                if i == 0 {
-                                                                    playing.clear();
-                                                                }
-                                                                if let Value::Object(pp) = p {
-                                                                    if let Some(Value::String(
-                                                                        name,
-                                                                    )) = pp.get("name")
-                                                                    {
-                                                                        playing.push_str(&format!(
-                                                                            "{}\n",
-                                                                            name
-                                                                        ));
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                        e.field(
-                                                            &format!("Players: {}/{}", online, max),
-                                                            playing,
-                                                            false,
-                                                        );
-                                                    }
-                                                }
-                                            }
-
-                                            // Mods (later forge version)
-                                            if let Some(Value::Object(forgedata)) =
-                                                map.get("forgeData")
-                                            {
-                                                if let Some(Value::Array(mods)) =
-                                                    forgedata.get("mods")
-                                                {
-                                                    let mut mods_str = String::new();
-
-                                                    for (i, mcmod) in mods.iter().enumerate() {
-                                                        // Limit to 10 mods otherwise message gets too long to send
-                                                        if i > 10 {
-                                                            mods_str.push_str("And more...");
-                                                            break;
-                                                        }
-
-                                                        if let Some(Value::String(name)) =
-                                                            mcmod.get("modId")
-                                                        {
-                                                            if name == "minecraft" {
-                                                                continue;
-                                                            }
-
-                                                            if let Some(Value::String(version)) =
-                                                                mcmod.get("modmarker")
-                                                            {
-                                                                mods_str.push_str(&format!(
-                                                                    "{} - {}\n",
-                                                                    name, version
-                                                                ));
-                                                            }
-                                                        }
-                                                    }
-
-                                                    e.field("Mods", mods_str, false);
-                                                }
-                                            }
-
-                                            // Mods (earlier forge version)
-                                            if let Some(Value::Object(forgedata)) =
-                                                map.get("modinfo")
-                                            {
-                                                if let Some(Value::Array(mods)) =
-                                                    forgedata.get("modList")
-                                                {
-                                                    let mut mods_str = String::new();
-
-                                                    for (i, mcmod) in mods.iter().enumerate() {
-                                                        // Limit to 10 mods otherwise message gets too long to send
-                                                        if i > 10 {
-                                                            mods_str.push_str("And more...");
-                                                            break;
-                                                        }
-
-                                                        if let Some(Value::String(name)) =
-                                                            mcmod.get("modid")
-                                                        {
-                                                            if name == "minecraft" {
-                                                                continue;
-                                                            }
-
-                                                            if let Some(Value::String(version)) =
-                                                                mcmod.get("version")
-                                                            {
-                                                                mods_str.push_str(&format!(
-                                                                    "{} - {}\n",
-                                                                    name, version
-                                                                ));
-                                                            }
-                                                        }
-                                                    }
-
-                                                    e.field("Mods", mods_str, false);
-                                                }
-                                            }
-                                        } else {
-                                            e.color(Color::from_rgb(255, 0, 0));
-                                            e.description(
-                                                "Error interpretting JSON response: Not an Object",
-                                            );
-                                        }
-                                    }
-                                    Err(err) => {
-                                        e.color(Color::from_rgb(255, 0, 0));
-                                        e.description(&format!(
-                                            "Error interpretting JSON response: {}",
-                                            err
-                                        ));
-                                    }
-                                }
-                                e
-                            });
-
-                            // Upload favicon
-                            match icon {
-                                None => {}
-                                Some(bytes) => {
-                                    m.add_file(AttachmentType::Bytes {
-                                        data: Cow::from(bytes),
-                                        filename: url,
-                                    });
-                                }
-                            }
-
-                            m
-                        })
-                        .await
-                        .unwrap();
-                }
-                Err(err) => {
-                    resp.edit(ctx, |m| {
-                        m.embed(|e| {
-                            e.title("Server name/IP");
-                            e.color(Color::from_rgb(255, 0, 0));
-                            e.description(format!(
-                                "Failed to retrieve status from server: {}",
-                                err
-                            ));
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            msg.reply(ctx, "Attach a file made by `export` to import it")
+                .await?;
+            return Ok(());
+        }
+    };
 
-                            e
-                        });
+    if attachment.size > MAX_IMPORT_BYTES {
+        msg.reply(
+            ctx,
+            format!(
+                "The file is too big, it can be at most {} KiB",
+                MAX_IMPORT_BYTES / 1024
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
 
-                        m
-                    })
-                    .await?;
-                }
-            }
+    let mut export = match Export::parse(&attachment.download().await?) {
+        Ok(export) => export,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
         }
-        Err(err) => {
-            resp.edit(&ctx.http, |m| {
-                m.embed(|e| {
-                    e.title(&format!("{}", serv));
-                    e.color(Color::from_rgb(255, 0, 0));
-                    e.description(&format!("Couldn't connect to server: {}", err));
+    };
+
+    // Channels only have their own active server in the guild they're in
+    match msg.guild_id {
+        Some(guild) => {
+            let channels = guild.channels(ctx).await?;
+            export
+                .channels
+                .retain(|c| channels.contains_key(&ChannelId(c.channel)));
+        }
+        None => export.channels.clear(),
+    }
 
-                    e
-                });
+    let scope = Scope::of(msg);
+
+    // Shows what would change without touching the saved list
+    let preview = match SERVERS.read(scope, |gs| export.apply(&mut gs.clone(), mode)) {
+        Ok(preview) => preview,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    let question = format!("Import {} servers?\n{}", export.servers.len(), preview);
+    if !confirm(ctx, msg, &question).await? {
+        return Ok(());
+    }
+
+    // Applied again, in case the list changed while waiting for confirmation
+    let changes = match SERVERS.update(scope, |gs| export.apply(gs, mode)) {
+        Ok(changes) => changes,
+        Err(why) => {
+            msg.reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    msg.reply(
+        ctx,
+        format!("Imported {} servers.\n{}", export.servers.len(), changes),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// Removes all servers from list
+#[command]
+#[bucket = "commands"]
+#[checks(Manage)]
+async fn removeall(ctx: &Context, msg: &Message) -> CommandResult {
+    let scope = Scope::of(msg);
+
+    let count = SERVERS.read(scope, |gs| gs.servers.len());
+
+    if count == 0 {
+        msg.reply(ctx, "There are no saved servers").await?;
+        return Ok(());
+    }
+
+    if !confirm(ctx, msg, &format!("Remove all {} saved servers?", count)).await? {
+        return Ok(());
+    }
+
+    SERVERS.update(scope, GuildServers::clear);
+
+    msg.reply(ctx, "All servers have been removed!").await?;
+
+    Ok(())
+}
+
+// Prints all saved servers for this guild in Discord
+#[command]
+#[bucket = "commands"]
+#[usage("[Tag]")]
+async fn servers(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() > 1 {
+        return args::usage_error(ctx, msg, &SERVERS_COMMAND_OPTIONS).await;
+    }
+
+    let tag = args::next(&mut args);
+
+    let scope = Scope::of(msg);
+
+    let gs: GuildServers = SERVERS.read(scope, GuildServers::clone);
+
+    if gs.servers.is_empty() {
+        msg.reply(ctx, "There are no saved servers").await?;
+        return Ok(());
+    }
+
+    // Only servers with the tag, grouped by their tags with untagged servers last
+    let mut saved: Vec<&MCServer> = gs
+        .servers
+        .iter()
+        .filter(|s| tag.as_ref().is_none_or(|t| s.has_tag(t)))
+        .collect();
+    saved.sort_by_key(|s| {
+        let tags: Vec<String> = s.tags.iter().map(|t| t.to_lowercase()).collect();
+        (tags.is_empty(), tags)
+    });
+
+    if saved.is_empty() {
+        msg.reply(
+            ctx,
+            format!(
+                "There are no saved servers tagged {}",
+                tag.unwrap_or_default()
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Create message
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Servers");
+
+                let active = match gs.active_server() {
+                    Some(s) => s.to_string(),
+                    None => String::from("None"),
+                };
+                e.field("Active", active, false);
+
+                // Channels with their own active server
+                if !gs.channels.is_empty() {
+                    let mut channels: Vec<ChannelId> = gs.channels.keys().copied().collect();
+                    channels.sort();
+
+                    let mut bindings = String::new();
+                    for channel in channels {
+                        if let Some(s) = gs.active_in(channel) {
+                            bindings.push_str(&format!("{} - {}\n", channel.mention(), s));
+                        }
+                    }
+
+                    e.field("Channels", bindings, false);
+                }
+
+                let mut servers = String::new();
+
+                for s in saved {
+                    if s.tags.is_empty() {
+                        servers.push_str(&format!("{}\n", s));
+                    } else {
+                        servers.push_str(&format!("{} `{}`\n", s, s.tags.join(", ")));
+                    }
+                }
+
+                match &tag {
+                    Some(tag) => e.field(format!("Tagged {}", tag), servers, false),
+                    None => e.field("Saved", servers, false),
+                };
+
+                e
+            });
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+// Changes the prefix commands use in this guild
+#[command]
+#[bucket = "commands"]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[usage("[Prefix]")]
+async fn setprefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    if args.len() > 1 {
+        return args::usage_error(ctx, msg, &SETPREFIX_COMMAND_OPTIONS).await;
+    }
+
+    let guild = msg.guild_id.unwrap();
+    let prefix = args::next(&mut args);
+
+    if let Some(prefix) = &prefix {
+        if let Err(why) = settings::validate_prefix(prefix) {
+            msg.reply(ctx, why).await?;
+            return Ok(());
+        }
+    }
+
+    SETTINGS.update(guild, |s| s.prefix = prefix);
+
+    msg.reply(
+        ctx,
+        format!(
+            "Commands here now start with `{}`",
+            SETTINGS.prefix(Some(guild))
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// Shows or changes who may use the commands that edit the server list
+#[command]
+#[bucket = "commands"]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[usage("[<Command> <everyone|manage|roles @Role...>]")]
+async fn permissions(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = msg.guild_id.unwrap();
+
+    let command = match args::next(&mut args) {
+        Some(command) => command,
+        // List what every command is set to
+        None => {
+            let mut lines = String::new();
+            for command in permissions::MANAGED_COMMANDS {
+                lines.push_str(&format!(
+                    "`{}` - {}\n",
+                    command,
+                    permissions::get(id, command)
+                ));
+            }
+
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        e.title("Permissions");
+                        e.description(lines);
+
+                        e
+                    })
+                })
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if !permissions::MANAGED_COMMANDS.contains(&command.as_str()) {
+        msg.reply(
+            ctx,
+            &format!(
+                "`{}` can't be restricted. Commands that can: {}",
+                command,
+                permissions::MANAGED_COMMANDS.join(", ")
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let access = match args::next(&mut args).as_deref() {
+        Some("everyone") if args.is_empty() => Access::Everyone,
+        Some("manage") if args.is_empty() => Access::ManageServer,
+        Some("roles") if !msg.mention_roles.is_empty() => Access::Roles(msg.mention_roles.clone()),
+        _ => return args::usage_error(ctx, msg, &PERMISSIONS_COMMAND_OPTIONS).await,
+    };
+
+    // Saved before replying, so a reply that fails to send doesn't lose the change
+    let reply = format!("`{}` can now be used by {}", command, access);
+    permissions::set(id, &command, access);
+
+    msg.reply(ctx, &reply).await?;
+
+    Ok(())
+}
+
+// Creates a new HTTP API token for this guild and sends it privately
+#[command]
+#[bucket = "commands"]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn apitoken(ctx: &Context, msg: &Message) -> CommandResult {
+    let id = msg.guild_id.unwrap();
+
+    let token = api::new_token(id);
+
+    msg.author
+        .direct_message(ctx, |m| {
+            m.content(format!(
+                "API token for guild {}:\n`{}`\nAny previous token no longer works.",
+                id, token
+            ))
+        })
+        .await?;
+
+    msg.reply(ctx, "Sent you a new API token in a direct message.")
+        .await?;
+
+    Ok(())
+}
+
+// How many servers statusall, and the poller, ping at once
+const PING_PARALLELISM: usize = 8;
+// Discord allows 4096 characters in an embed description
+const STATUSALL_PAGE_LEN: usize = 4000;
+
+// Prints a one line status of every saved server for this guild in Discord
+#[command]
+#[bucket = "pings"]
+async fn statusall(ctx: &Context, msg: &Message) -> CommandResult {
+    let scope = Scope::of(msg);
+
+    // Copy the servers out so the lock isn't held while pinging
+    let servers = SERVERS.read(scope, |gs| gs.servers.clone());
+
+    if servers.is_empty() {
+        msg.reply(ctx, "There are no saved servers").await?;
+        return Ok(());
+    }
+
+    let mut resp = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Server status");
+                e.color(config::get().colors.pending.0);
+                e.description(format!("Pinging {} servers...", servers.len()));
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    let lookups = lookup_all(&servers).await;
+
+    let online = lookups.iter().filter(|l| l.result.is_ok()).count();
+    let lines: Vec<String> = servers
+        .iter()
+        .zip(&lookups)
+        .map(|(serv, lookup)| status_line(serv, lookup.result.as_ref().as_ref()))
+        .collect();
+
+    // Split into pages that fit in an embed
+    let mut pages: Vec<String> = vec![String::new()];
+    for line in lines {
+        let page = pages.last_mut().unwrap();
+        if !page.is_empty() && page.len() + line.len() + 1 > STATUSALL_PAGE_LEN {
+            pages.push(String::new());
+        }
+
+        let page = pages.last_mut().unwrap();
+        page.push_str(&line);
+        page.push('\n');
+    }
+
+    let color = online_color(online, servers.len());
+    let total = pages.len();
+
+    for (i, page) in pages.iter().enumerate() {
+        let title = if total > 1 {
+            format!("Server status ({}/{})", i + 1, total)
+        } else {
+            String::from("Server status")
+        };
+        let footer = format!("{}/{} online", online, servers.len());
+        let build = |e: &mut CreateEmbed| {
+            e.title(&title);
+            e.color(color);
+            e.description(page);
+            e.footer(|f| f.text(&footer));
+        };
+
+        // The first page replaces the pinging message
+        if i == 0 {
+            resp.edit(&ctx.http, |m| {
+                m.embed(|e| {
+                    build(e);
+                    e
+                })
+            })
+            .await?;
+        } else {
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        build(e);
+                        e
+                    })
+                })
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Combined status of every saved server with a tag
+async fn group_status(ctx: &Context, msg: &Message, tag: &str) -> CommandResult {
+    let scope = Scope::of(msg);
+
+    // Copy the servers out so the lock isn't held while pinging
+    let servers: Vec<MCServer> = SERVERS.read(scope, |gs| {
+        gs.servers
+            .iter()
+            .filter(|s| s.has_tag(tag))
+            .cloned()
+            .collect()
+    });
+
+    if servers.is_empty() {
+        msg.reply(ctx, format!("There are no saved servers tagged {}", tag))
+            .await?;
+        return Ok(());
+    }
+
+    let title = format!("Group {}", tag);
+
+    let mut resp = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(&title);
+                e.color(config::get().colors.pending.0);
+                e.description(format!("Pinging {} servers...", servers.len()));
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    let lookups = lookup_all(&servers).await;
+
+    let mut totals = GroupTotals::default();
+    let mut backends = String::new();
+
+    for (serv, lookup) in servers.iter().zip(&lookups) {
+        let result = lookup.result.as_ref().as_ref();
+        backends.push_str(&status_line(serv, result));
+        backends.push('\n');
+        totals.add(result);
+    }
+
+    // Keep the description within what an embed allows
+    if backends.len() > STATUSALL_PAGE_LEN {
+        let mut end = STATUSALL_PAGE_LEN;
+        while !backends.is_char_boundary(end) {
+            end -= 1;
+        }
+        backends.truncate(end);
+        backends.push('…');
+    }
+
+    let players = players_field(&totals.sample, totals.players_online);
+
+    resp.edit(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title(&title);
+            e.color(online_color(totals.online, servers.len()));
+            e.description(&backends);
+            e.field(
+                "Players",
+                format!("{}/{}", totals.players_online, totals.players_max),
+                true,
+            );
+            e.field(
+                "Online",
+                format!("{}/{}", totals.online, servers.len()),
+                true,
+            );
+            if let Some(players) = &players {
+                e.field("Playing", players, false);
+            }
+
+            e
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+// Players and servers online across a group
+#[derive(Debug, Default)]
+struct GroupTotals<'a> {
+    online: usize,
+    players_online: i64,
+    players_max: i64,
+    sample: Vec<&'a str>,
+}
+
+impl<'a> GroupTotals<'a> {
+    fn add(&mut self, result: Result<&'a ServerStatus, &PingError>) {
+        let status = match result {
+            Ok(status) => status,
+            Err(_) => return,
+        };
+        self.online += 1;
+
+        if let Some(players) = &status.players {
+            self.players_online += players.online;
+            self.players_max += players.max;
+
+            // A player can show up on more than one backend while switching between them
+            for player in &players.sample {
+                if !self.sample.contains(&player.name.as_str()) {
+                    self.sample.push(&player.name);
+                }
+            }
+        }
+    }
+}
+
+// Discord allows 1024 characters in an embed field
+const FIELD_LEN: usize = 1024;
+
+// Names of the players in a group, noting how many more there are than fit or were sent
+fn players_field(sample: &[&str], online: i64) -> Option<String> {
+    if sample.is_empty() {
+        return None;
+    }
+
+    let mut names = String::new();
+    let mut shown = 0;
+    for name in sample {
+        // Leave room for the count of players not shown
+        if names.len() + name.len() + 2 > FIELD_LEN - 32 {
+            break;
+        }
+
+        if !names.is_empty() {
+            names.push_str(", ");
+        }
+        names.push_str(name);
+        shown += 1;
+    }
+
+    let hidden = online - shown;
+    if hidden > 0 {
+        names.push_str(&format!(" and {} more", hidden));
+    }
+
+    Some(names)
+}
+
+// Green when every server is up, red when none are, and yellow otherwise
+fn online_color(online: usize, total: usize) -> Color {
+    if online == total {
+        config::get().colors.online.0
+    } else if online == 0 {
+        config::get().colors.offline.0
+    } else {
+        config::get().colors.pending.0
+    }
+}
+
+// Gets the status of several servers, keeping their order while only pinging a few at a time
+async fn lookup_all(servers: &[MCServer]) -> Vec<cache::Lookup> {
+    let timeout = config::get().ping.timeout();
+
+    futures::stream::iter(servers.to_vec())
+        .map(|serv| async move { lookup_status(&serv, timeout).await })
+        .buffered(PING_PARALLELISM)
+        .collect()
+        .await
+}
+
+// Gets the status of a server from the cache, or by pinging it
+async fn lookup_status(serv: &MCServer, timeout: Duration) -> cache::Lookup {
+    let addr = match network::resolve(&serv.ip).await {
+        Ok(addr) => addr,
+        Err(err) => {
+            record_status(serv, Err(&err)).await;
+            return cache::Lookup {
+                result: Arc::new(Err(err)),
+                age: None,
+            };
+        }
+    };
+
+    let options = PingOptions {
+        timeout: Some(timeout),
+        ..PingOptions::default()
+    };
+
+    cache::status_or_ping(addr, timeout, || async {
+        let result = mcstatus::ping_resolved(addr, &serv.host(), &options).await;
+        record_status(serv, result.as_ref()).await;
+        result
+    })
+    .await
+}
+
+// A single line summary of a server for statusall
+fn status_line(serv: &MCServer, result: Result<&ServerStatus, &PingError>) -> String {
+    let name = serv.name.as_deref().unwrap_or(&serv.ip);
+
+    match result {
+        Ok(status) => {
+            let mut line = format!("🟢 **{}**", name);
+
+            if let Some(players) = &status.players {
+                line.push_str(&format!(" · {}/{} players", players.online, players.max));
+            }
+
+            let version: String = status.version.name.chars().take(32).collect();
+            line.push_str(&format!(" · {}", version));
+
+            if let Some(latency) = status.latency {
+                line.push_str(&format!(" · {} ms", latency.as_millis()));
+            }
+
+            line
+        }
+        Err(err) => format!("🔴 **{}** · offline ({})", name, err.kind()),
+    }
+}
+
+// Gets and prints the status of a Minecraft server
+async fn get_status(ctx: &Context, msg: &Message, serv: &MCServer) -> CommandResult {
+    // Results are shared by resolved address, so different names for one server share a ping
+    let addr = match network::resolve(&serv.ip).await {
+        Ok(addr) => addr,
+        Err(err) => {
+            record_status(serv, Err(&err)).await;
+
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        e.title(serv);
+                        e.color(config::get().colors.offline.0);
+                        e.description(err);
+
+                        e
+                    });
+
+                    m
+                })
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    // Answer straight away if the server was pinged recently
+    if let Some(lookup) = cache::fresh(addr).await {
+        match lookup.result.as_ref() {
+            Ok(status) => send_status(ctx, msg, serv, status, lookup.age).await?,
+            Err(err) => {
+                msg.channel_id
+                    .send_message(&ctx.http, |m| {
+                        m.embed(|e| {
+                            e.title(serv);
+                            e.color(config::get().colors.offline.0);
+                            e.description(err);
+                            checked_footer(e, lookup.age);
+
+                            e
+                        });
+
+                        m
+                    })
+                    .await?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    // Send discord message saying it's connecting
+    let mut resp = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(serv);
+                e.color(config::get().colors.pending.0);
+                e.description("Connecting to server...");
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    // Only one of the requests for a server waiting here actually pings it
+    let timeout = config::get().ping.timeout();
+    let connecting = &mut resp;
+    let lookup = cache::status_or_ping(addr, timeout, || async move {
+        // The timeout covers connecting too, so a server that never answers can't hold up the cache
+        let result = tokio::time::timeout(timeout, async {
+            // Connect to server
+            let mut stream = TcpStream::connect(addr).await.map_err(PingError::Connect)?;
+
+            // Edit connecting message to connected if it connects
+            let edited = connecting
+                .edit(&ctx.http, |m| {
+                    m.embed(|e| {
+                        e.title(serv);
+                        e.color(config::get().colors.online.0);
+                        e.description("Connected!");
+
+                        e
+                    });
+
+                    m
+                })
+                .await;
+            if let Err(why) = edited {
+                METRICS.record_discord_error();
+                error!("Failed to edit status message: {:?}", why);
+            }
+
+            // Get server status
+            mcstatus::ping_stream(&mut stream, &serv.host(), &PingOptions::default()).await
+        })
+        .await
+        .unwrap_or(Err(PingError::Timeout));
+
+        record_status(serv, result.as_ref()).await;
+        result
+    })
+    .await;
+
+    match lookup.result.as_ref() {
+        Ok(status) => {
+            // Delete connected message as we have gotten the status
+            resp.delete(ctx).await?;
+
+            send_status(ctx, msg, serv, status, lookup.age).await?;
+        }
+        Err(err) => {
+            resp.edit(ctx, |m| {
+                m.embed(|e| {
+                    e.title(serv);
+                    e.color(config::get().colors.offline.0);
+                    e.description(err);
+                    checked_footer(e, lookup.age);
+
+                    e
+                });
 
                 m
             })
@@ -797,3 +1575,312 @@ async fn get_status(ctx: &Context, msg: &Message, serv: &MCServer) -> CommandRes
 
     Ok(())
 }
+
+// Notes how old a status is when it came from the cache rather than a ping just now
+fn checked_footer(e: &mut CreateEmbed, age: Option<Duration>) {
+    if let Some(age) = age {
+        let checked = match age.as_secs() {
+            0 => String::from("Checked just now"),
+            secs @ 1..=59 => format!("Checked {}s ago", secs),
+            secs => format!("Checked {}m {}s ago", secs / 60, secs % 60),
+        };
+
+        e.footer(|f| f.text(checked));
+    }
+}
+
+// Keeps metrics and the cached status of saved servers, only counting failures for addresses typed into statusip
+async fn record_status(serv: &MCServer, result: Result<&ServerStatus, &PingError>) {
+    match (&serv.name, result) {
+        (Some(_), result) => record_saved_status(&serv.ip, result).await,
+        (None, Err(err)) => METRICS.record_failure(err),
+        (None, Ok(_)) => {}
+    }
+}
+
+async fn record_saved_status(address: &str, result: Result<&ServerStatus, &PingError>) {
+    METRICS.record_status(address, result);
+    cache::store(address, result).await;
+}
+
+// Stops tracking a server that is no longer saved in any guild
+async fn forget_saved_status(address: &str) {
+    METRICS.forget_server(address);
+    cache::forget(address).await;
+}
+
+// Creates the status message for a server in discord
+async fn send_status(
+    ctx: &Context,
+    msg: &Message,
+    serv: &MCServer,
+    status: &ServerStatus,
+    age: Option<Duration>,
+) -> CommandResult {
+    let url = String::from("favicon.png");
+
+    // Favicon
+    let icon = status.favicon.as_ref().map(|favicon| {
+        base64::decode(
+            favicon
+                .replace("\n", "")
+                .trim_start_matches("data:image/png;base64,"),
+        )
+    });
+
+    // Players
+    let players = ListPages::new(
+        "players",
+        "Players",
+        status
+            .players
+            .iter()
+            .flat_map(|p| p.sample.iter().map(|p| p.name.clone()))
+            .collect(),
+    );
+
+    // Mods
+    let mods: Vec<Mod> = status
+        .mods()
+        .into_iter()
+        .filter(|m| m.id != "minecraft")
+        .collect();
+    let mods = ListPages::new(
+        "mods",
+        "Mods",
+        mods.iter()
+            .map(|m| format!("{} - {}", m.id, m.version))
+            .collect(),
+    );
+
+    let message = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            // Creat message embed
+            m.embed(|e| {
+                status_embed(e, serv, status, age, &players, &mods);
+
+                match &icon {
+                    Some(Ok(_)) => {
+                        e.thumbnail(format!("attachment://{}", url));
+                    }
+                    Some(Err(err)) => {
+                        e.description(format!("Failed to decode favicon: {}", err));
+                    }
+                    None => {}
+                }
+
+                e
+            });
+
+            // Upload favicon
+            if let Some(Ok(bytes)) = icon {
+                m.add_file(AttachmentType::Bytes {
+                    data: Cow::from(bytes),
+                    filename: url.clone(),
+                });
+            }
+
+            if players.is_paged() || mods.is_paged() {
+                m.components(|c| {
+                    pages::add_buttons(c, &[&players, &mods]);
+                    c
+                });
+            }
+
+            m
+        })
+        .await?;
+
+    if players.is_paged() || mods.is_paged() {
+        // Page in the background so the command doesn't wait on the buttons
+        let ctx = ctx.clone();
+        let serv = serv.clone();
+        let status = status.clone();
+        tokio::spawn(async move {
+            if let Err(why) =
+                page_status(&ctx, message, &serv, &status, age, players, mods, &url).await
+            {
+                METRICS.record_discord_error();
+                error!("Paging status of {} failed: {:?}", serv, why);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Turns the pages of a status message's lists until nobody has pressed a button for a while
+#[allow(clippy::too_many_arguments)]
+async fn page_status(
+    ctx: &Context,
+    mut message: Message,
+    serv: &MCServer,
+    status: &ServerStatus,
+    age: Option<Duration>,
+    mut players: ListPages,
+    mut mods: ListPages,
+    url: &str,
+) -> Result<(), serenity::Error> {
+    while let Some(interaction) = message
+        .await_component_interaction(ctx)
+        .timeout(config::get().buttons.page_timeout())
+        .await
+    {
+        let id = &interaction.data.custom_id;
+        if !players.press(id) && !mods.press(id) {
+            continue;
+        }
+
+        interaction
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage);
+                r.interaction_response_data(|d| {
+                    d.create_embed(|e| {
+                        status_embed(e, serv, status, age, &players, &mods);
+
+                        // The favicon is still attached to the message
+                        if message.attachments.iter().any(|a| a.filename == url) {
+                            e.thumbnail(format!("attachment://{}", url));
+                        }
+
+                        e
+                    });
+                    d.components(|c| {
+                        pages::add_buttons(c, &[&players, &mods]);
+                        c
+                    })
+                })
+            })
+            .await?;
+    }
+
+    // Remove the buttons once they stop working
+    message.edit(ctx, |m| m.components(|c| c)).await
+}
+
+// Fills in everything in a status embed except the favicon
+fn status_embed(
+    e: &mut CreateEmbed,
+    serv: &MCServer,
+    status: &ServerStatus,
+    age: Option<Duration>,
+    players: &ListPages,
+    mods: &ListPages,
+) {
+    e.title(serv);
+    e.color(config::get().colors.online.0);
+
+    // MOTD
+    let motd = status.motd();
+    if !motd.is_empty() {
+        e.description(motd);
+    }
+
+    // Version number
+    e.field("Version", &status.version.name, false);
+
+    // Players
+    if let Some(count) = &status.players {
+        let playing = if players.is_empty() {
+            String::from("No players online.")
+        } else {
+            players.current()
+        };
+
+        e.field(
+            format!(
+                "Players: {}/{}{}",
+                count.online,
+                count.max,
+                players.page_suffix()
+            ),
+            playing,
+            false,
+        );
+    }
+
+    // Mods
+    if !mods.is_empty() {
+        e.field(format!("Mods{}", mods.page_suffix()), mods.current(), false);
+    }
+
+    checked_footer(e, age);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(json: &str) -> ServerStatus {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn matches_tags_ignoring_case() {
+        let serv = MCServer {
+            id: 1,
+            ip: "mc.example.com:25565".to_string(),
+            name: Some("Lobby".to_string()),
+            tags: vec!["Network".to_string(), "hub".to_string()],
+        };
+
+        assert!(serv.has_tag("network"));
+        assert!(serv.has_tag("HUB"));
+        assert!(!serv.has_tag("net"));
+        assert!(!serv.has_tag(""));
+    }
+
+    #[test]
+    fn totals_group_status() {
+        let lobby = status(
+            r#"{"players":{"max":20,"online":2,"sample":[{"name":"Notch"},{"name":"jeb_"}]}}"#,
+        );
+        let survival = status(r#"{"players":{"max":50,"online":4,"sample":[{"name":"jeb_"}]}}"#);
+        let empty = status(r#"{"description":"No players field"}"#);
+
+        let mut totals = GroupTotals::default();
+        totals.add(Ok(&lobby));
+        totals.add(Err(&PingError::Timeout));
+        totals.add(Ok(&survival));
+        totals.add(Ok(&empty));
+
+        assert_eq!(totals.online, 3);
+        assert_eq!((totals.players_online, totals.players_max), (6, 70));
+        // Players switching between backends are only listed once
+        assert_eq!(totals.sample, ["Notch", "jeb_"]);
+    }
+
+    #[test]
+    fn counts_players_not_shown() {
+        assert_eq!(players_field(&[], 5), None);
+        assert_eq!(
+            players_field(&["Notch", "jeb_"], 2).as_deref(),
+            Some("Notch, jeb_")
+        );
+
+        // Servers only send a sample of who's online
+        assert_eq!(
+            players_field(&["Notch", "jeb_"], 7).as_deref(),
+            Some("Notch, jeb_ and 5 more")
+        );
+
+        // A sample longer than the count never shows a negative number
+        assert_eq!(
+            players_field(&["Notch", "jeb_"], 1).as_deref(),
+            Some("Notch, jeb_")
+        );
+
+        // Names that don't fit in the field are counted instead
+        let names: Vec<String> = (0..200).map(|i| format!("player_{:03}", i)).collect();
+        let sample: Vec<&str> = names.iter().map(String::as_str).collect();
+        let field = players_field(&sample, 200).unwrap();
+        assert!(field.len() <= FIELD_LEN, "{}", field.len());
+        let shown = field.split(" and ").next().unwrap().split(", ").count() as i64;
+        assert!(
+            field.ends_with(&format!(" and {} more", 200 - shown)),
+            "{}",
+            field
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use mcstatus::{PingError, ServerStatus};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, GaugeVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics for the servers the bot watches and the bot itself
+pub struct Metrics {
+    registry: Registry,
+
+    server_up: IntGaugeVec,
+    players_online: IntGaugeVec,
+    players_max: IntGaugeVec,
+    latency: GaugeVec,
+    protocol_version: IntGaugeVec,
+
+    commands: IntCounterVec,
+    status_failures: IntCounterVec,
+    discord_errors: IntCounter,
+}
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("mcstatus".to_string()), None)
+            .expect("valid metrics namespace");
+
+        let server_gauge = |name: &str, help: &str| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), &["address"]).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+
+        let server_up = server_gauge(
+            "server_up",
+            "Whether the server answered its last status query",
+        );
+        let players_online = server_gauge("server_players_online", "Players online on the server");
+        let players_max = server_gauge("server_players_max", "Player slots on the server");
+        let protocol_version = server_gauge(
+            "server_protocol_version",
+            "Protocol version the server reports",
+        );
+
+        let latency = GaugeVec::new(
+            Opts::new(
+                "server_latency_seconds",
+                "Round trip time of the last status ping",
+            ),
+            &["address"],
+        )
+        .unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+
+        let commands = IntCounterVec::new(
+            Opts::new("commands_total", "Discord commands handled"),
+            &["command"],
+        )
+        .unwrap();
+        registry.register(Box::new(commands.clone())).unwrap();
+
+        let status_failures = IntCounterVec::new(
+            Opts::new(
+                "status_failures_total",
+                "Failed status queries by kind of error",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(status_failures.clone()))
+            .unwrap();
+
+        let discord_errors = IntCounter::new(
+            "discord_api_errors_total",
+            "Errors returned by the Discord API",
+        )
+        .unwrap();
+        registry.register(Box::new(discord_errors.clone())).unwrap();
+
+        Metrics {
+            registry,
+            server_up,
+            players_online,
+            players_max,
+            latency,
+            protocol_version,
+            commands,
+            status_failures,
+            discord_errors,
+        }
+    }
+
+    /// Records the outcome of a status query of a saved server, from a command or the poller
+    pub fn record_status(&self, address: &str, result: Result<&ServerStatus, &PingError>) {
+        match result {
+            Ok(status) => {
+                self.server_up.with_label_values(&[address]).set(1);
+                self.protocol_version
+                    .with_label_values(&[address])
+                    .set(status.version.protocol as i64);
+
+                // Leaving out what the server didn't report, rather than keeping an old value
+                match &status.players {
+                    Some(players) => {
+                        self.players_online
+                            .with_label_values(&[address])
+                            .set(players.online);
+                        self.players_max
+                            .with_label_values(&[address])
+                            .set(players.max);
+                    }
+                    None => self.forget_players(address),
+                }
+
+                match status.latency {
+                    Some(latency) => self
+                        .latency
+                        .with_label_values(&[address])
+                        .set(latency.as_secs_f64()),
+                    None => {
+                        let _ = self.latency.remove_label_values(&[address]);
+                    }
+                }
+            }
+            Err(err) => {
+                // A server that's down has no players or latency, whatever it last reported
+                self.server_up.with_label_values(&[address]).set(0);
+                self.forget_players(address);
+                let _ = self.latency.remove_label_values(&[address]);
+                let _ = self.protocol_version.remove_label_values(&[address]);
+                self.record_failure(err);
+            }
+        }
+    }
+
+    fn forget_players(&self, address: &str) {
+        let _ = self.players_online.remove_label_values(&[address]);
+        let _ = self.players_max.remove_label_values(&[address]);
+    }
+
+    /// Counts a failed status query without tracking the server, for addresses that aren't saved
+    pub fn record_failure(&self, err: &PingError) {
+        self.status_failures.with_label_values(&[err.kind()]).inc();
+    }
+
+    /// Drops the gauges of a server that is no longer saved anywhere
+    pub fn forget_server(&self, address: &str) {
+        let _ = self.server_up.remove_label_values(&[address]);
+        self.forget_players(address);
+        let _ = self.latency.remove_label_values(&[address]);
+        let _ = self.protocol_version.remove_label_values(&[address]);
+    }
+
+    pub fn record_command(&self, command: &str) {
+        self.commands.with_label_values(&[command]).inc();
+    }
+
+    pub fn record_discord_error(&self) {
+        self.discord_errors.inc();
+    }
+
+    /// Renders every metric in the Prometheus text format
+    fn render(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("metrics encode to text");
+        buf
+    }
+}
+
+/// Serves `/metrics` on the address until the bot stops
+pub async fn serve(addr: SocketAddr) {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            let response = match (req.method(), req.uri().path()) {
+                (&Method::GET, "/metrics") => Response::builder()
+                    .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                    .body(Body::from(METRICS.render())),
+                _ => Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty()),
+            };
+
+            Ok::<_, Infallible>(response.unwrap())
+        }))
+    });
+
+    info!("Serving metrics on http://{}/metrics", addr);
+    if let Err(e) = Server::bind(&addr).serve(make_service).await {
+        error!("Metrics server stopped: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn rendered(metrics: &Metrics) -> String {
+        String::from_utf8(metrics.render()).unwrap()
+    }
+
+    #[test]
+    fn renders_server_status() {
+        let metrics = Metrics::new();
+        let mut status: ServerStatus = serde_json::from_str(
+            r#"{"version":{"name":"1.18.1","protocol":757},"players":{"max":20,"online":3}}"#,
+        )
+        .unwrap();
+        status.latency = Some(Duration::from_millis(250));
+
+        metrics.record_status("mc.example.com", Ok(&status));
+        metrics.record_status("down.example.com", Err(&PingError::Timeout));
+        metrics.record_command("status");
+        metrics.record_command("status");
+        metrics.record_discord_error();
+
+        let text = rendered(&metrics);
+        for line in [
+            r#"mcstatus_server_up{address="mc.example.com"} 1"#,
+            r#"mcstatus_server_up{address="down.example.com"} 0"#,
+            r#"mcstatus_server_players_online{address="mc.example.com"} 3"#,
+            r#"mcstatus_server_players_max{address="mc.example.com"} 20"#,
+            r#"mcstatus_server_protocol_version{address="mc.example.com"} 757"#,
+            r#"mcstatus_server_latency_seconds{address="mc.example.com"} 0.25"#,
+            r#"mcstatus_status_failures_total{kind="timeout"} 1"#,
+            r#"mcstatus_commands_total{command="status"} 2"#,
+            "mcstatus_discord_api_errors_total 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} missing from\n{}",
+                line,
+                text
+            );
+        }
+
+        // A failed server keeps no player counts
+        assert!(!text.contains(r#"server_players_online{address="down.example.com"}"#));
+    }
+
+    #[test]
+    fn clears_player_counts_when_a_server_goes_down() {
+        let metrics = Metrics::new();
+        let mut status: ServerStatus = serde_json::from_str(
+            r#"{"version":{"name":"1.18.1","protocol":757},"players":{"max":20,"online":3}}"#,
+        )
+        .unwrap();
+        status.latency = Some(Duration::from_millis(40));
+
+        metrics.record_status("mc.example.com", Ok(&status));
+        assert!(rendered(&metrics).contains(r#"server_players_online{address="mc.example.com"} 3"#));
+
+        metrics.record_status("mc.example.com", Err(&PingError::Timeout));
+        let text = rendered(&metrics);
+        assert!(text.contains(r#"mcstatus_server_up{address="mc.example.com"} 0"#));
+        for gauge in [
+            "server_players_online",
+            "server_players_max",
+            "server_latency_seconds",
+            "server_protocol_version",
+        ] {
+            assert!(
+                !text.contains(&format!(r#"{}{{address="mc.example.com"}}"#, gauge)),
+                "{} kept\n{}",
+                gauge,
+                text
+            );
+        }
+
+        // Back up without a player list or pong, so neither comes back
+        let status: ServerStatus = serde_json::from_str(r#"{"description":"Back"}"#).unwrap();
+        metrics.record_status("mc.example.com", Ok(&status));
+        let text = rendered(&metrics);
+        assert!(text.contains(r#"mcstatus_server_up{address="mc.example.com"} 1"#));
+        assert!(!text.contains(r#"server_players_online{address="mc.example.com"}"#));
+        assert!(!text.contains(r#"server_latency_seconds{address="mc.example.com"}"#));
+    }
+
+    #[test]
+    fn forgets_removed_servers() {
+        let metrics = Metrics::new();
+        let status: ServerStatus =
+            serde_json::from_str(r#"{"players":{"max":5,"online":1}}"#).unwrap();
+        metrics.record_status("old.example.com", Ok(&status));
+        metrics.record_status("kept.example.com", Ok(&status));
+
+        metrics.forget_server("old.example.com");
+
+        let text = rendered(&metrics);
+        assert!(!text.contains("old.example.com"), "{}", text);
+        assert!(text.contains("kept.example.com"), "{}", text);
+    }
+}
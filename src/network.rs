@@ -1,178 +1,279 @@
-pub mod packets;
-use log::{error, info};
-use packets::*;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpStream,
-};
-
-pub mod types;
-
 use std::{
-    error::Error,
-    io::{Read, Write},
-    sync::mpsc::{Receiver, SendError, Sender},
+    fmt::Display,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    str::FromStr,
 };
 
-use self::types::*;
+use mcstatus::PingError;
+use serde::Deserialize;
 
-pub const PROTOCOL_1_17_1: VarInt = VarInt(756);
-pub const PROTOCOL_1_18_1: VarInt = VarInt(757);
+use crate::config;
 
-pub enum ServerState {
-    Status,
-    Login,
-    Play,
-}
+/// Ranges the bot won't connect to unless the config says otherwise
+pub const DEFAULT_DENY: &[&str] = &[
+    // "This network", private, shared (carrier-grade NAT) and loopback
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    // Link-local, which is where cloud metadata services live
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    // Benchmarking, which some networks route internally
+    "198.18.0.0/15",
+    // Multicast, reserved and broadcast
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    // Unspecified, loopback and the deprecated IPv4-compatible addresses like ::127.0.0.1
+    "::/96",
+    // Unique local and link-local
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
 
-/// Attempts to get the next packet in the TcpStream
-/// Panics if the TcpStream could not read the next data to prevent correupted packets and unexpected behaviour
-/// # Returns
-///
-/// Returns a Decoded Packet ready for processing, or None if there was no packet to receive.
-///
-async fn next_packet(stream: &mut TcpStream, state: ServerState) -> Option<DecodedPacket> {
-    // Check there is packet and get size of it
-    match VarInt::from_stream(stream).await {
-        Ok(Some(VarInt(0))) => {
-            return None;
-        }
-        Ok(Some(VarInt(len))) => {
-            let mut buf = vec![0; len as usize];
+/// A block of IP addresses, like `10.0.0.0/8`, or a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
 
-            match stream.read_exact(&mut buf).await {
-                Ok(_) => {
-                    // Return packet without decompressing
-                    return Some(decode_packet(buf, &state));
-                }
-                Err(e) => {
-                    error!("Failed reading packet from stream: {}", e);
-                    panic!("Force stopped to prevent unexpected behaviour.");
-                }
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
             }
-        }
-        Ok(None) => {
-            error!("Failed to read packet!");
-            return None;
-        }
-        Err(_) => {
-            return None;
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
         }
     }
 }
 
-pub async fn status(
-    stream: &mut TcpStream,
-) -> Result<StatusResponse, Box<dyn Error + Send + Sync>> {
-    use std::net::SocketAddr;
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<IpRange, String> {
+        let invalid = || format!("`{}` should be an IP range like 10.0.0.0/8", range);
+
+        let (addr, prefix) = match range.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (range, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
 
-    // Extracts local address from TcpStream
-    let local_addr = match stream.local_addr() {
-        Err(e) => {
-            error!("Failed to get local address from TCPStream!");
-            return Err(Box::new(e));
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => bits,
+        };
+        if prefix > bits {
+            return Err(invalid());
         }
-        Ok(addr) => match addr {
-            SocketAddr::V4(local) => local.ip().to_string(),
-            SocketAddr::V6(local) => local.ip().to_string(),
-        },
+
+        Ok(IpRange { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(range: String) -> Result<IpRange, String> {
+        range.parse()
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+// The IPv4 address an IPv6 one reaches: IPv4-mapped (::ffff:127.0.0.1),
+// NAT64 (64:ff9b::127.0.0.1) and 6to4 (2002:7f00:1::)
+fn embedded_ipv4(ip: IpAddr) -> Option<IpAddr> {
+    let v6 = match ip {
+        IpAddr::V6(v6) => v6,
+        IpAddr::V4(_) => return None,
     };
 
-    // Construct and send handshake and login packets
-    let handshake = DecodedPacket::Handshake(Handshake {
-        protocol_version: VarInt(-1),
-        origin: MCString(local_addr),
-        port: Short(0),
-        next_state: HandshakeMode::Status,
-    });
-
-    send_packet(stream, handshake).await?;
-    info!("Sent handshake");
-    send_packet(stream, DecodedPacket::StatusRequest(StatusRequest {})).await?;
-    info!("Sent status request");
-    send_packet(stream, DecodedPacket::StatusPing(StatusPing {})).await?;
-    info!("Sent ping");
-
-    loop {
-        match next_packet(stream, ServerState::Status).await {
-            Some(DecodedPacket::StatusResponse(response)) => {
-                return Ok(response);
-            }
-            None => {
-                info!("No response");
-            }
-            Some(DecodedPacket::StatusPong(payload)) => {
-                info!("Got pong");
-            }
-            _ => {
-                break;
-            }
-        }
+    if let Some(v4) = v6.to_ipv4_mapped() {
+        return Some(IpAddr::V4(v4));
     }
 
-    todo!();
-    // TODO - Finish this.
+    let bits = u128::from(v6);
+    if bits >> 32 == 0x0064_ff9b_0000_0000_0000_0000 {
+        Some(IpAddr::V4(Ipv4Addr::from(bits as u32)))
+    } else if bits >> 112 == 0x2002 {
+        Some(IpAddr::V4(Ipv4Addr::from((bits >> 80) as u32)))
+    } else {
+        None
+    }
 }
 
-/// Sends a packet to the server
+/// Whether the bot may connect to an address: allowed ranges win over denied ones
 ///
-/// # Returns
+/// IPv6 addresses with an IPv4 address inside match ranges of either, so `::ffff:127.0.0.1`
+/// is denied by `127.0.0.0/8` and `64:ff9b::8.8.8.8` by `64:ff9b::/96`.
+pub fn allowed(ip: IpAddr, deny: &[IpRange], allow: &[IpRange]) -> bool {
+    let v4 = embedded_ipv4(ip);
+    let matches = |range: &IpRange| range.contains(ip) || v4.is_some_and(|v4| range.contains(v4));
+    allow.iter().any(matches) || !deny.iter().any(matches)
+}
+
+/// Looks up a server's address, refusing ones the config doesn't allow the bot to connect to
 ///
-/// * `Some(())` if the packet is successfully sent
-/// * `None` if it is not
-async fn send_packet(
-    stream: &mut TcpStream,
-    packet: DecodedPacket,
-) -> Result<bool, Box<dyn Error + Send + Sync>> {
-    // Attempt to encode packet
-    match packet.encode() {
-        Some(pack) => {
-            // Send without compression
-            let bytes = pack.get_bytes_with_length();
-            match stream.write(bytes.as_slice()).await {
-                Ok(_) => Ok(true),
-                Err(e) => {
-                    error!("Failed to write to TcpStream: {}", e);
-                    return Err(Box::new(e));
-                }
-            }
-        }
-        // Packet encode failure
-        None => {
-            error!("Failed to encode packet: {:?}", packet);
-            return Ok(false);
-        }
+/// The first allowed address the name resolves to is returned, and it's the only one connected to,
+/// so a second lookup can't give a different answer. SRV records aren't looked up, so there is
+/// no other target that could get around the check.
+pub async fn resolve(addr: &str) -> Result<SocketAddr, PingError> {
+    let resolved = mcstatus::resolve_all(addr).await?;
+
+    let network = &config::get().network;
+    match resolved
+        .iter()
+        .find(|a| allowed(a.ip(), &network.deny, &network.allow))
+    {
+        Some(allowed) => Ok(*allowed),
+        None => Err(PingError::Connect(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} isn't an address this bot connects to", resolved[0].ip()),
+        ))),
     }
 }
 
-// Struct to hold communication channels between network manager and other threads
-pub struct NetworkChannel {
-    pub send: Sender<NetworkCommand>,
-    pub recv: Receiver<NetworkCommand>,
+/// The default deny list, parsed
+pub fn default_deny() -> Vec<IpRange> {
+    DEFAULT_DENY.iter().map(|r| r.parse().unwrap()).collect()
 }
 
-impl NetworkChannel {
-    pub fn send_packet(&self, packet: DecodedPacket) -> Result<(), SendError<NetworkCommand>> {
-        match self.send.send(NetworkCommand::SendPacket(packet)) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                error!("Failed to communicate with network commander: {:?}", e);
-                Err(e)
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(
+            "10.0.0.0/8".parse::<IpRange>().unwrap().to_string(),
+            "10.0.0.0/8"
+        );
+        assert_eq!(
+            "1.2.3.4".parse::<IpRange>().unwrap().to_string(),
+            "1.2.3.4/32"
+        );
+        assert_eq!("::1".parse::<IpRange>().unwrap().to_string(), "::1/128");
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("10.0.0/8".parse::<IpRange>().is_err());
+        assert!("::/x".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn denies_internal_addresses_by_default() {
+        let deny = default_deny();
+
+        for internal in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.31.255.255",
+            "192.168.0.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fe80::1",
+            "fd12:3456::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "198.18.0.1",
+            "198.19.255.255",
+            "::127.0.0.1",
+            "::a9fe:a9fe",
+            "64:ff9b::127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "2002:c0a8:0101::1",
+        ] {
+            assert!(!allowed(ip(internal), &deny, &[]), "{}", internal);
+        }
+
+        for public in [
+            "1.1.1.1",
+            "172.32.0.1",
+            "198.20.0.1",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+            "64:ff9b::8.8.8.8",
+            "2002:808:808::1",
+        ] {
+            assert!(allowed(ip(public), &deny, &[]), "{}", public);
         }
     }
-}
 
-// Types of Messages that can be sent
-#[derive(Debug)]
-pub enum NetworkCommand {
-    Ok,
-    Error(Box<dyn Error + Send + Sync>),
-    Disconnect,
-    // Login(protocol, port, name)
-    Login(VarInt, Short, MCString),
-    Status,
-
-    SendPacket(DecodedPacket),
-    ReceivePacket(DecodedPacket),
+    #[test]
+    fn allow_list_wins_over_deny_list() {
+        let deny = default_deny();
+        let allow = ["192.168.1.0/24".parse().unwrap()];
+
+        assert!(allowed(ip("192.168.1.20"), &deny, &allow));
+        assert!(!allowed(ip("192.168.2.20"), &deny, &allow));
+
+        // Everything is allowed with an empty deny list
+        assert!(allowed(ip("127.0.0.1"), &[], &[]));
+    }
+
+    #[test]
+    fn ipv6_ranges_match_addresses_with_ipv4_inside() {
+        let nat64: [IpRange; 1] = ["64:ff9b::/96".parse().unwrap()];
+        assert!(!allowed(ip("64:ff9b::8.8.8.8"), &nat64, &[]));
+        assert!(allowed(ip("8.8.8.8"), &nat64, &[]));
+
+        let six_to_four: [IpRange; 1] = ["2002::/16".parse().unwrap()];
+        assert!(!allowed(ip("2002:808:808::1"), &six_to_four, &[]));
+
+        let mapped: [IpRange; 1] = ["::ffff:0:0/96".parse().unwrap()];
+        assert!(!allowed(ip("::ffff:8.8.8.8"), &mapped, &[]));
+
+        // Allowing either form lets the address through
+        let deny = default_deny();
+        assert!(allowed(
+            ip("::ffff:192.168.1.20"),
+            &deny,
+            &["192.168.1.0/24".parse().unwrap()]
+        ));
+        assert!(allowed(
+            ip("64:ff9b::10.0.0.1"),
+            &deny,
+            &["64:ff9b::/96".parse().unwrap()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_refuses_denied_addresses() {
+        for addr in ["127.0.0.1:25565", "[::1]:25565", "10.0.0.1", "localhost"] {
+            match resolve(addr).await {
+                Err(PingError::Connect(e)) => {
+                    assert_eq!(e.kind(), io::ErrorKind::PermissionDenied, "{}", addr)
+                }
+                other => panic!("{} should be refused, got {:?}", addr, other),
+            }
+        }
+
+        assert_eq!(
+            resolve("8.8.8.8:25565").await.unwrap(),
+            SocketAddr::from(([8, 8, 8, 8], 25565))
+        );
+    }
 }
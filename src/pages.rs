@@ -0,0 +1,166 @@
+use serenity::builder::CreateComponents;
+use serenity::model::interactions::message_component::ButtonStyle;
+
+// Lines shown per page, short enough that a page always fits in a 1024 character embed field
+const PAGE_SIZE: usize = 10;
+const MAX_LINE_LEN: usize = 100;
+
+/// A list shown one page at a time in an embed field
+pub struct ListPages {
+    // Prefix of the custom ids of this list's buttons
+    id: &'static str,
+    label: &'static str,
+    lines: Vec<String>,
+    page: usize,
+}
+
+impl ListPages {
+    pub fn new(id: &'static str, label: &'static str, lines: Vec<String>) -> ListPages {
+        let lines = lines
+            .into_iter()
+            .map(|line| match line.char_indices().nth(MAX_LINE_LEN) {
+                Some((end, _)) => format!("{}…", &line[..end]),
+                None => line,
+            })
+            .collect();
+
+        ListPages {
+            id,
+            label,
+            lines,
+            page: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.lines.len().div_ceil(PAGE_SIZE).max(1)
+    }
+
+    pub fn is_paged(&self) -> bool {
+        self.page_count() > 1
+    }
+
+    /// The lines on the current page
+    pub fn current(&self) -> String {
+        self.lines
+            .iter()
+            .skip(self.page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    /// Which page is showing, for the field name, or nothing if there is only one
+    pub fn page_suffix(&self) -> String {
+        if self.is_paged() {
+            format!(" (page {}/{})", self.page + 1, self.page_count())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Turns the page if the button pressed belongs to this list
+    pub fn press(&mut self, custom_id: &str) -> bool {
+        match custom_id.strip_prefix(self.id) {
+            Some("_prev") if self.page > 0 => self.page -= 1,
+            Some("_next") if self.page + 1 < self.page_count() => self.page += 1,
+            Some("_prev") | Some("_next") => {}
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// Adds a row of previous/next buttons for each list with more than one page
+pub fn add_buttons(c: &mut CreateComponents, lists: &[&ListPages]) {
+    for list in lists.iter().filter(|l| l.is_paged()) {
+        c.create_action_row(|r| {
+            r.create_button(|b| {
+                b.custom_id(format!("{}_prev", list.id));
+                b.label(format!("◀ {}", list.label));
+                b.style(ButtonStyle::Secondary);
+                b.disabled(list.page == 0)
+            });
+            r.create_button(|b| {
+                b.custom_id(format!("{}_next", list.id));
+                b.label(format!("{} ▶", list.label));
+                b.style(ButtonStyle::Secondary);
+                b.disabled(list.page + 1 >= list.page_count())
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn splits_lines_into_pages() {
+        let mut list = ListPages::new("servers", "Servers", numbered(25));
+        assert_eq!(list.page_count(), 3);
+        assert!(list.is_paged());
+        assert_eq!(list.page_suffix(), " (page 1/3)");
+        assert_eq!(list.current().lines().count(), PAGE_SIZE);
+        assert!(list.current().starts_with("line 1\n"));
+
+        assert!(list.press("servers_next"));
+        assert!(list.press("servers_next"));
+        assert_eq!(list.page_suffix(), " (page 3/3)");
+        assert_eq!(
+            list.current(),
+            "line 21\nline 22\nline 23\nline 24\nline 25\n"
+        );
+
+        // Stays on the last page, and the press is still this list's
+        assert!(list.press("servers_next"));
+        assert_eq!(list.page_suffix(), " (page 3/3)");
+
+        assert!(list.press("servers_prev"));
+        assert!(list.current().starts_with("line 11\n"));
+
+        // Buttons of other lists leave it alone
+        assert!(!list.press("players_next"));
+        assert!(list.current().starts_with("line 11\n"));
+    }
+
+    #[test]
+    fn short_lists_have_one_page() {
+        let list = ListPages::new("players", "Players", numbered(PAGE_SIZE));
+        assert_eq!(list.page_count(), 1);
+        assert!(!list.is_paged());
+        assert_eq!(list.page_suffix(), "");
+
+        let mut empty = ListPages::new("players", "Players", Vec::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.page_count(), 1);
+        assert_eq!(empty.current(), "");
+        assert!(empty.press("players_prev"));
+        assert_eq!(empty.current(), "");
+    }
+
+    #[test]
+    fn cuts_long_lines() {
+        let long = "é".repeat(MAX_LINE_LEN + 20);
+        let list = ListPages::new("mods", "Mods", vec![long, "short".to_string()]);
+
+        let current = list.current();
+        let lines: Vec<&str> = current.lines().collect();
+        assert_eq!(lines[0].chars().count(), MAX_LINE_LEN + 1);
+        assert!(lines[0].ends_with('…'));
+        assert_eq!(lines[1], "short");
+
+        // A full page of the longest lines still fits in an embed field
+        let list = ListPages::new("mods", "Mods", vec!["x".repeat(500); PAGE_SIZE]);
+        assert!(list.current().chars().count() <= 1024);
+    }
+}
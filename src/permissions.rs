@@ -0,0 +1,164 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    client::Context,
+    framework::standard::{macros::check, Args, CommandOptions, Reason},
+    model::{
+        channel::Message,
+        id::{GuildId, RoleId},
+    },
+};
+
+use crate::settings::SETTINGS;
+
+/// Commands that change a guild's server list, and so can be restricted
+pub const MANAGED_COMMANDS: &[&str] = &[
+    "add",
+    "remove",
+    "removeall",
+    "setactive",
+    "clearactive",
+    "rename",
+    "setip",
+    "tag",
+    "untag",
+    "import",
+];
+
+/// Who may run a command in a guild
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// Members with the Manage Server permission
+    ManageServer,
+    /// Members with any of these roles, as well as those with Manage Server
+    Roles(Vec<RoleId>),
+    Everyone,
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Access::ManageServer => write!(f, "Manage Server"),
+            Access::Roles(roles) => {
+                let roles: Vec<String> = roles.iter().map(|r| format!("<@&{}>", r)).collect();
+                write!(f, "Manage Server or {}", roles.join(", "))
+            }
+            Access::Everyone => write!(f, "Everyone"),
+        }
+    }
+}
+
+/// Who may run a command in a guild. Anything the guild hasn't set is Manage Server only
+pub fn get(guild: GuildId, command: &str) -> Access {
+    SETTINGS
+        .get(guild)
+        .permissions
+        .remove(command)
+        .unwrap_or(Access::ManageServer)
+}
+
+pub fn set(guild: GuildId, command: &str, access: Access) {
+    SETTINGS.update(guild, |s| match access {
+        // The default isn't stored, so guilds that go back to it are forgotten
+        Access::ManageServer => {
+            s.permissions.remove(command);
+        }
+        access => {
+            s.permissions.insert(command.to_string(), access);
+        }
+    });
+}
+
+// Whether a member with these roles, who may or may not manage the guild, has the access
+fn allows(access: &Access, can_manage: bool, roles: &[RoleId]) -> bool {
+    can_manage
+        || match access {
+            Access::Everyone => true,
+            Access::Roles(allowed) => roles.iter().any(|r| allowed.contains(r)),
+            Access::ManageServer => false,
+        }
+}
+
+// Lets a command through if the guild allows the author to run it
+#[check]
+#[name = "Manage"]
+async fn manage_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    let guild = match msg.guild_id {
+        Some(guild) => guild,
+        None => return Ok(()),
+    };
+
+    let command = options.names[0];
+    let access = get(guild, command);
+
+    if access == Access::Everyone {
+        return Ok(());
+    }
+
+    let member = msg
+        .member(ctx)
+        .await
+        .map_err(|e| Reason::Log(format!("Couldn't get member: {}", e)))?;
+
+    let can_manage = member
+        .permissions(ctx)
+        .await
+        .map(|p| p.manage_guild())
+        .unwrap_or(false);
+
+    if allows(&access, can_manage, &member.roles) {
+        Ok(())
+    } else {
+        Err(Reason::User(format!(
+            "You need {} to use `{}` here.",
+            access, command
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::GuildSettings;
+
+    use super::*;
+
+    #[test]
+    fn checks_access() {
+        let roles = Access::Roles(vec![RoleId(1), RoleId(2)]);
+
+        // Managers can always run everything
+        for access in [&Access::ManageServer, &roles, &Access::Everyone] {
+            assert!(allows(access, true, &[]));
+        }
+
+        assert!(!allows(&Access::ManageServer, false, &[RoleId(1)]));
+        assert!(allows(&roles, false, &[RoleId(3), RoleId(2)]));
+        assert!(!allows(&roles, false, &[RoleId(3)]));
+        assert!(!allows(&roles, false, &[]));
+        assert!(allows(&Access::Everyone, false, &[]));
+    }
+
+    #[test]
+    fn access_is_kept_in_guild_settings() {
+        let guild = GuildId(37);
+        assert_eq!(get(guild, "add"), Access::ManageServer);
+
+        set(guild, "add", Access::Everyone);
+        set(guild, "remove", Access::Roles(vec![RoleId(5)]));
+        assert_eq!(get(guild, "add"), Access::Everyone);
+        assert_eq!(get(guild, "remove"), Access::Roles(vec![RoleId(5)]));
+        assert_eq!(get(GuildId(38), "add"), Access::ManageServer);
+
+        // Going back to the default forgets the guild
+        set(guild, "add", Access::ManageServer);
+        set(guild, "remove", Access::ManageServer);
+        assert_eq!(SETTINGS.get(guild), GuildSettings::default());
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+
+use futures::StreamExt;
+use mcstatus::PingOptions;
+
+use crate::{
+    cache, config, forget_saved_status, network, origin, record_saved_status, storage::SERVERS,
+    PING_PARALLELISM,
+};
+
+/// Queries every saved server on an interval so metrics and the API stay current between commands
+pub async fn poll_servers() {
+    let mut known: HashSet<String> = HashSet::new();
+
+    loop {
+        // Read each round, so reloading the config changes them
+        let ping = config::get().ping.clone();
+        let options = PingOptions {
+            timeout: Some(ping.poll_timeout()),
+            ..PingOptions::default()
+        };
+
+        // Copy the addresses out so no lock is held while pinging
+        let addresses: HashSet<String> = SERVERS
+            .snapshot()
+            .into_iter()
+            .flat_map(|(_, gs)| gs.servers.into_iter().map(|s| s.ip))
+            .collect();
+
+        for removed in known.difference(&addresses) {
+            forget_saved_status(removed).await;
+        }
+        known = addresses.clone();
+
+        // Only a few at a time however many are saved, and servers someone asked about
+        // recently aren't pinged again
+        let (ping, options) = (&ping, &options);
+        futures::stream::iter(addresses)
+            .map(|addr| async move {
+                let resolved = match network::resolve(&addr).await {
+                    Ok(resolved) => resolved,
+                    Err(err) => return record_saved_status(&addr, Err(&err)).await,
+                };
+
+                cache::status_or_ping(resolved, ping.poll_timeout(), || async {
+                    let result = mcstatus::ping_resolved(resolved, &origin(&addr), options).await;
+                    record_saved_status(&addr, result.as_ref()).await;
+                    result
+                })
+                .await;
+            })
+            .buffer_unordered(PING_PARALLELISM)
+            .collect::<Vec<()>>()
+            .await;
+
+        tokio::time::sleep(ping.poll_interval()).await;
+    }
+}
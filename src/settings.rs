@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::Path,
+    sync::{PoisonError, RwLock},
+};
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    client::Context,
+    model::{channel::Message, id::GuildId},
+};
+use tokio::sync::Notify;
+
+use crate::{
+    config,
+    permissions::Access,
+    storage::{read_json, save_on_change},
+};
+
+// Longest prefix a guild can set
+const MAX_PREFIX_LEN: usize = 10;
+
+/// Options a guild has changed from the bot's defaults
+///
+/// Every field is optional, so files saved before a setting existed still load.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Prefix used instead of the one in the config
+    pub prefix: Option<String>,
+    /// Who may use each managed command, for the ones not left at Manage Server
+    pub permissions: HashMap<String, Access>,
+    /// Hash of the guild's HTTP API token, the token itself is only sent to whoever asked for it
+    pub api_token_hash: Option<String>,
+}
+
+/// Settings of every guild that has changed any
+#[derive(Default)]
+pub struct SettingsStore {
+    guilds: RwLock<HashMap<GuildId, GuildSettings>>,
+    // Wakes up persist after settings change
+    changed: Notify,
+}
+
+impl SettingsStore {
+    /// A guild's settings, which are all unset if it never changed any
+    pub fn get(&self, guild: GuildId) -> GuildSettings {
+        self.guilds
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&guild)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Changes a guild's settings, forgetting the guild once they're back to the defaults
+    pub fn update<R>(&self, guild: GuildId, f: impl FnOnce(&mut GuildSettings) -> R) -> R {
+        let mut guilds = self.guilds.write().unwrap_or_else(PoisonError::into_inner);
+
+        let settings = guilds.entry(guild).or_default();
+        let result = f(settings);
+        if *settings == GuildSettings::default() {
+            guilds.remove(&guild);
+        }
+
+        self.changed.notify_one();
+        result
+    }
+
+    /// The prefix commands use in a guild, or in DMs when there's no guild
+    pub fn prefix(&self, guild: Option<GuildId>) -> String {
+        guild
+            .and_then(|guild| self.get(guild).prefix)
+            .unwrap_or_else(|| config::get().discord.prefix.clone())
+    }
+
+    /// Reads settings saved by persist, if the file exists
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        let guilds: Vec<(GuildId, GuildSettings)> = read_json(path)?.unwrap_or_default();
+        *self.guilds.write().unwrap_or_else(PoisonError::into_inner) = guilds.into_iter().collect();
+
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Vec<(GuildId, GuildSettings)> {
+        self.guilds
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(guild, settings)| (*guild, settings.clone()))
+            .collect()
+    }
+
+    /// Writes every guild's settings to a file whenever they change
+    pub async fn persist(&self, path: &Path) {
+        save_on_change(path, &self.changed, || self.snapshot()).await
+    }
+}
+
+pub static SETTINGS: Lazy<SettingsStore> = Lazy::new(SettingsStore::default);
+
+/// Gives the framework the prefix of the guild a message was sent in
+pub fn prefix_hook<'fut>(_: &'fut Context, msg: &'fut Message) -> BoxFuture<'fut, Option<String>> {
+    Box::pin(async move { Some(SETTINGS.prefix(msg.guild_id)) })
+}
+
+/// Checks a guild can use a prefix
+pub fn validate_prefix(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() || prefix.chars().count() > MAX_PREFIX_LEN {
+        return Err(format!(
+            "Prefixes must be from 1 to {} characters long",
+            MAX_PREFIX_LEN
+        ));
+    }
+
+    if prefix.contains(|c: char| c.is_whitespace() || c == '`') {
+        return Err(String::from("Prefixes can't contain spaces or `"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::id::RoleId;
+
+    use super::*;
+
+    #[test]
+    fn guilds_fall_back_to_the_configured_prefix() {
+        let store = SettingsStore::default();
+        let default = config::get().discord.prefix.clone();
+
+        store.update(GuildId(1), |s| s.prefix = Some(String::from("!")));
+        assert_eq!(store.prefix(Some(GuildId(1))), "!");
+        assert_eq!(store.prefix(Some(GuildId(2))), default);
+        assert_eq!(store.prefix(None), default);
+
+        // Going back to the defaults forgets the guild
+        store.update(GuildId(1), |s| s.prefix = None);
+        assert!(store.snapshot().is_empty());
+    }
+
+    #[test]
+    fn saved_settings_load_again() {
+        let path = std::env::temp_dir().join(format!("settings-{}.json", std::process::id()));
+
+        let store = SettingsStore::default();
+        store.update(GuildId(1), |s| {
+            s.prefix = Some(String::from("mc!"));
+            s.permissions
+                .insert(String::from("add"), Access::Roles(vec![RoleId(7)]));
+            s.api_token_hash = Some(String::from("hash"));
+        });
+        std::fs::write(&path, serde_json::to_vec(&store.snapshot()).unwrap()).unwrap();
+
+        let loaded = SettingsStore::default();
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let settings = loaded.get(GuildId(1));
+        assert_eq!(settings.prefix.as_deref(), Some("mc!"));
+        assert_eq!(
+            settings.permissions.get("add"),
+            Some(&Access::Roles(vec![RoleId(7)]))
+        );
+        assert_eq!(settings.api_token_hash.as_deref(), Some("hash"));
+
+        // Settings added later are left unset in older files
+        let old: GuildSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(old, GuildSettings::default());
+    }
+
+    #[test]
+    fn rejects_bad_prefixes() {
+        assert!(validate_prefix("!").is_ok());
+        assert!(validate_prefix("mc!").is_ok());
+        assert!(validate_prefix("").is_err());
+        assert!(validate_prefix("a b").is_err());
+        assert!(validate_prefix("waytoolongprefix").is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::Duration,
+};
+
+use log::error;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serenity::model::{
+    channel::Message,
+    id::{ChannelId, GuildId, UserId},
+};
+use tokio::sync::Notify;
+
+use crate::MCServer;
+
+// How long changes are collected before the lists are written to disk
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Whose server list a command works on: a guild's shared list, or a user's personal one in DMs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    Guild(GuildId),
+    User(UserId),
+}
+
+impl Scope {
+    /// The guild a message was sent in, or its author when it was sent in a DM
+    pub fn of(msg: &Message) -> Scope {
+        match msg.guild_id {
+            Some(guild) => Scope::Guild(guild),
+            None => Scope::User(msg.author.id),
+        }
+    }
+}
+
+// The saved servers of a guild, or of a user in DMs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuildServers {
+    // ID of the server `status` uses when none is given, if one is set
+    pub active: Option<u64>,
+    pub servers: Vec<MCServer>,
+    // Channels with their own active server, used instead of `active` there
+    pub channels: HashMap<ChannelId, u64>,
+    // ID given to the last server saved, so removed servers' IDs are never reused
+    #[serde(default)]
+    last_id: u64,
+}
+
+impl GuildServers {
+    // Index of the saved server with this name
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.servers.iter().position(|s| s.has_name(name))
+    }
+
+    // Saved server with this ID
+    pub fn get(&self, id: u64) -> Option<&MCServer> {
+        self.servers.iter().find(|s| s.id == id)
+    }
+
+    // Saves a server with a new ID. The first server saved to an empty list becomes active
+    pub fn add(&mut self, mut serv: MCServer) -> u64 {
+        self.last_id += 1;
+        serv.id = self.last_id;
+
+        if self.servers.is_empty() && self.active.is_none() {
+            self.active = Some(serv.id);
+        }
+
+        self.servers.push(serv);
+        self.last_id
+    }
+
+    // The guild's active server, if it has one
+    pub fn active_server(&self) -> Option<&MCServer> {
+        self.active.and_then(|id| self.get(id))
+    }
+
+    // Server `status` uses in a channel, falling back to the guild's active server
+    pub fn active_in(&self, channel: ChannelId) -> Option<&MCServer> {
+        match self.channels.get(&channel) {
+            Some(id) => self.get(*id),
+            None => self.active_server(),
+        }
+    }
+
+    // Removes a server. Anything it was active for is left without an active server
+    pub fn remove(&mut self, ind: usize) -> MCServer {
+        let id = self.servers[ind].id;
+
+        if self.active == Some(id) {
+            self.active = None;
+        }
+
+        // Channels using the removed server fall back to the guild's active server
+        self.channels.retain(|_, active| *active != id);
+
+        self.servers.remove(ind)
+    }
+
+    // Removes every server
+    pub fn clear(&mut self) {
+        self.active = None;
+        self.servers.clear();
+        self.channels.clear();
+    }
+}
+
+/// Saved servers of every guild and user, each list behind its own lock
+///
+/// Lists are only reached through closures that can't await, so a lock can never be held while
+/// pinging a server or talking to Discord. Anything needed for that is copied out first.
+#[derive(Default)]
+pub struct ServerStore {
+    // Only written to the first time a guild or user changes their list
+    lists: RwLock<HashMap<Scope, Arc<Mutex<GuildServers>>>>,
+    // Wakes up persist after a list changes
+    changed: Notify,
+}
+
+impl ServerStore {
+    fn list(&self, scope: Scope) -> Option<Arc<Mutex<GuildServers>>> {
+        self.lists
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&scope)
+            .cloned()
+    }
+
+    /// Looks at a list of servers, which is empty if nothing was ever saved to it
+    pub fn read<R>(&self, scope: Scope, f: impl FnOnce(&GuildServers) -> R) -> R {
+        match self.list(scope) {
+            Some(gs) => f(&gs.lock().unwrap_or_else(PoisonError::into_inner)),
+            None => f(&GuildServers::default()),
+        }
+    }
+
+    /// Changes a list of servers, creating its record if it doesn't have one
+    pub fn update<R>(&self, scope: Scope, f: impl FnOnce(&mut GuildServers) -> R) -> R {
+        let gs = match self.list(scope) {
+            Some(gs) => gs,
+            None => self
+                .lists
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(scope)
+                .or_default()
+                .clone(),
+        };
+
+        let mut gs = gs.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = f(&mut gs);
+        self.changed.notify_one();
+        result
+    }
+
+    /// A copy of every list of servers
+    pub fn snapshot(&self) -> Vec<(Scope, GuildServers)> {
+        // Copy the handles out first, so adding a list never waits on another list's lock
+        let lists: Vec<(Scope, Arc<Mutex<GuildServers>>)> = self
+            .lists
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(scope, gs)| (*scope, gs.clone()))
+            .collect();
+
+        lists
+            .into_iter()
+            .map(|(scope, gs)| {
+                (
+                    scope,
+                    gs.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Reads lists saved by persist, if the file exists
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        let lists: Vec<(Scope, GuildServers)> = read_json(path)?.unwrap_or_default();
+
+        *self.lists.write().unwrap_or_else(PoisonError::into_inner) = lists
+            .into_iter()
+            .map(|(scope, gs)| (scope, Arc::new(Mutex::new(gs))))
+            .collect();
+
+        Ok(())
+    }
+
+    /// Writes every list to a file whenever one changes
+    pub async fn persist(&self, path: &Path) {
+        save_on_change(path, &self.changed, || self.snapshot()).await
+    }
+}
+
+/// Reads a file written by save_on_change, or nothing if it doesn't exist yet
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Writes a snapshot of something to a file every time it changes
+pub async fn save_on_change<T: Serialize>(path: &Path, changed: &Notify, snapshot: impl Fn() -> T) {
+    // Written next to the file then moved over it, so a crash never leaves half a file
+    let partial = path.with_extension("partial");
+
+    loop {
+        changed.notified().await;
+        tokio::time::sleep(SAVE_DELAY).await;
+
+        let saved = async {
+            let data = serde_json::to_vec(&snapshot())?;
+            tokio::fs::write(&partial, data).await?;
+            tokio::fs::rename(&partial, path).await
+        }
+        .await;
+
+        if let Err(why) = saved {
+            error!("Failed to save to {}: {}", path.display(), why);
+        }
+    }
+}
+
+// Stores minecraft servers for each discord guild and for each user in DMs
+pub static SERVERS: Lazy<ServerStore> = Lazy::new(ServerStore::default);
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use mcstatus::{PingError, PingOptions};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::cache;
+
+    fn server(name: &str, ip: &str) -> MCServer {
+        MCServer::new(&ip.parse().unwrap(), Some(name.to_string()))
+    }
+
+    #[test]
+    fn reading_an_unknown_list_does_not_create_it() {
+        let store = ServerStore::default();
+        let guild = Scope::Guild(GuildId(1));
+
+        assert_eq!(store.read(guild, |gs| gs.servers.len()), 0);
+        assert!(store.snapshot().is_empty());
+
+        store.update(guild, |gs| gs.add(server("a", "localhost")));
+        assert_eq!(store.snapshot().len(), 1);
+        assert_eq!(store.read(guild, |gs| gs.find("A")), Some(0));
+    }
+
+    fn active_name(gs: &GuildServers, channel: u64) -> Option<&str> {
+        gs.active_in(ChannelId(channel))?.name.as_deref()
+    }
+
+    #[test]
+    fn channels_fall_back_to_the_active_server() {
+        let mut gs = GuildServers::default();
+        assert_eq!(active_name(&gs, 1), None);
+
+        let ids: Vec<u64> = ["survival", "creative", "modded"]
+            .into_iter()
+            .map(|name| gs.add(server(name, "localhost")))
+            .collect();
+        gs.active = Some(ids[1]);
+        gs.channels.insert(ChannelId(1), ids[2]);
+        gs.channels.insert(ChannelId(2), ids[0]);
+
+        assert_eq!(active_name(&gs, 1), Some("modded"));
+        assert_eq!(active_name(&gs, 3), Some("creative"));
+
+        // Removing a channel's server unbinds it, and the rest keep pointing at the same servers
+        gs.remove(0);
+        assert_eq!(active_name(&gs, 2), Some("creative"));
+        assert_eq!(active_name(&gs, 1), Some("modded"));
+        assert_eq!(active_name(&gs, 3), Some("creative"));
+    }
+
+    #[test]
+    fn removing_the_active_server_leaves_none_active() {
+        let mut gs = GuildServers::default();
+        let first = gs.add(server("survival", "localhost"));
+        let second = gs.add(server("creative", "localhost"));
+
+        // Only the first server saved becomes active on its own
+        assert_eq!(gs.active, Some(first));
+
+        gs.remove(0);
+        assert_eq!(gs.active, None);
+        assert_eq!(active_name(&gs, 1), None);
+
+        // IDs aren't reused, so nothing can end up pointing at a different server
+        let third = gs.add(server("survival", "localhost"));
+        assert!(third != first && third != second);
+        assert_eq!(gs.active, None);
+
+        gs.clear();
+        gs.add(server("modded", "localhost"));
+        assert_eq!(gs.active_server().unwrap().name.as_deref(), Some("modded"));
+    }
+
+    #[test]
+    fn saved_lists_load_again() {
+        let path = std::env::temp_dir().join(format!("servers-{}.json", std::process::id()));
+
+        let store = ServerStore::default();
+        store.update(Scope::Guild(GuildId(1)), |gs| {
+            let id = gs.add(server("survival", "localhost"));
+            gs.channels.insert(ChannelId(5), id);
+        });
+        store.update(Scope::User(UserId(2)), |gs| {
+            gs.add(server("mine", "[::1]:25566"));
+        });
+        std::fs::write(&path, serde_json::to_vec(&store.snapshot()).unwrap()).unwrap();
+
+        let loaded = ServerStore::default();
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let guild = loaded.read(Scope::Guild(GuildId(1)), GuildServers::clone);
+        assert_eq!(
+            guild.active_in(ChannelId(5)).unwrap().name.as_deref(),
+            Some("survival")
+        );
+        assert_eq!(
+            loaded.read(Scope::User(UserId(2)), |gs| gs.servers[0].ip.clone()),
+            "[::1]:25566"
+        );
+
+        // IDs keep counting up from where they were
+        let id = loaded.update(Scope::Guild(GuildId(1)), |gs| {
+            gs.add(server("creative", "localhost"))
+        });
+        assert_ne!(id, guild.servers[0].id);
+
+        // A missing file is an empty store
+        assert!(ServerStore::default().load(&path).is_ok());
+    }
+
+    #[test]
+    fn guild_and_user_lists_are_separate() {
+        let store = ServerStore::default();
+        let guild = Scope::Guild(GuildId(1));
+        let user = Scope::User(UserId(1));
+
+        store.update(guild, |gs| gs.add(server("shared", "localhost")));
+        store.update(user, |gs| gs.add(server("mine", "localhost")));
+
+        assert_eq!(store.read(guild, |gs| gs.find("mine")), None);
+        assert_eq!(store.read(user, |gs| gs.find("shared")), None);
+        assert_eq!(store.read(user, |gs| gs.find("mine")), Some(0));
+    }
+
+    // Load test: a server that never answers must not hold up commands, or other status requests for it
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn commands_stay_responsive_while_a_ping_hangs() {
+        // Accepts connections and never answers them
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let store = Arc::new(ServerStore::default());
+        let slow = Scope::Guild(GuildId(1));
+        store.update(slow, |gs| gs.add(server("hangs", &addr.to_string())));
+
+        let timeout = Duration::from_secs(1);
+        let options = PingOptions {
+            timeout: Some(timeout),
+            ..PingOptions::default()
+        };
+
+        // Pings the way status does, copying the server out and holding the address's cache slot
+        let status = |store: Arc<ServerStore>| {
+            let options = options.clone();
+            async move {
+                let serv = store.read(slow, |gs| gs.active_server().cloned().unwrap());
+                let addr: SocketAddr = serv.ip.parse().unwrap();
+                let lookup = cache::status_or_ping(addr, timeout, || {
+                    mcstatus::ping_resolved(addr, "localhost", &options)
+                })
+                .await;
+                lookup.result
+            }
+        };
+        let pinging = tokio::spawn(status(store.clone()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Asking about the same server again waits on the ping in flight, but not forever
+        let second = tokio::spawn(status(store.clone()));
+
+        // Other guilds add, look up and remove servers, and the hanging guild keeps changing too
+        let commands = (0..50u64).map(|g| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                let id = Scope::Guild(GuildId(100 + g));
+                for i in 0..100 {
+                    let name = format!("server{}", i);
+                    store.update(id, |gs| gs.add(server(&name, "localhost")));
+                    assert!(store.read(id, |gs| gs.find(&name)).is_some());
+                    tokio::task::yield_now().await;
+                }
+
+                store.update(id, GuildServers::clear);
+                store.update(slow, |gs| gs.servers[0].tags.clear());
+                store.snapshot();
+            })
+        });
+
+        let finished = tokio::time::timeout(timeout, futures::future::join_all(commands))
+            .await
+            .expect("commands were blocked by a hanging ping");
+        for command in finished {
+            command.unwrap();
+        }
+        assert!(!pinging.is_finished());
+
+        // Both status requests give up once the ping times out
+        let (first, second) = tokio::time::timeout(timeout * 2, async {
+            (pinging.await.unwrap(), second.await.unwrap())
+        })
+        .await
+        .expect("status waited on a hanging server past its timeout");
+        assert!(matches!(*first, Err(PingError::Timeout)));
+        assert!(second.is_err());
+    }
+}